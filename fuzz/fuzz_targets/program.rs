@@ -0,0 +1,27 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+
+use intcode::Number;
+use intcode::Program;
+
+// The first few values double as input, the rest is the program itself. Run
+// with `cargo fuzz run program -- -timeout=5` so that looping programs are
+// reported instead of stalling the fuzzer.
+fuzz_target!(|data: &[u8]| {
+    let numbers: Vec<Number> = data
+        .chunks_exact(8)
+        .map(|c| Number::from_le_bytes(c.try_into().unwrap()))
+        .collect();
+    if numbers.is_empty() {
+        return;
+    }
+
+    let (inputs, program) = numbers.split_at(numbers.len().min(4) / 2);
+    let mut p = Program::new(program.to_vec());
+    for i in inputs.iter() {
+        p.push_input(*i);
+    }
+    p.run_till_halted_or_blocked();
+    while p.get_output().is_some() {}
+});
@@ -0,0 +1,296 @@
+use std::collections::{BTreeMap, BTreeSet, HashSet};
+use std::fmt;
+use std::ops::Range;
+
+use intcode::Number;
+
+#[derive(Clone, Debug)]
+pub struct Instruction {
+    pub address: usize,
+    pub opcode: Number,
+    pub modes: Vec<Number>,
+    pub successors: Vec<usize>,
+    pub dynamic_jump: bool,
+}
+
+impl Instruction {
+    pub fn next_address(&self) -> usize {
+        self.address + self.modes.len() + 1
+    }
+
+    fn ends_block(&self) -> bool {
+        matches!(self.opcode, 5 | 6 | 99)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Block {
+    pub start: usize,
+    pub end: usize,
+    pub successors: Vec<usize>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Issue {
+    InvalidOpcode { address: usize, opcode: Number },
+    InvalidMode { address: usize, parameter: usize, mode: Number },
+    Truncated { address: usize },
+    SelfModifyingWrite { address: usize, target: usize },
+}
+
+impl fmt::Display for Issue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Issue::InvalidOpcode { address, opcode } => {
+                write!(f, "{}: invalid opcode {}", address, opcode)
+            }
+            Issue::InvalidMode { address, parameter, mode } => {
+                write!(f, "{}: invalid mode {} for parameter {}", address, mode, parameter)
+            }
+            Issue::Truncated { address } => {
+                write!(f, "{}: instruction runs past the end of the program", address)
+            }
+            Issue::SelfModifyingWrite { address, target } => {
+                write!(f, "{}: writes into code at {}", address, target)
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Analysis {
+    pub instructions: BTreeMap<usize, Instruction>,
+    pub blocks: Vec<Block>,
+    pub issues: Vec<Issue>,
+    /// Ranges of cells no instruction was decoded from. With dynamic jumps
+    /// in the program these may well be reached after all, for instance as
+    /// the return point of a subroutine, see `unreachable_is_exact`.
+    pub unreachable: Vec<Range<usize>>,
+}
+
+impl Analysis {
+    pub fn dynamic_jumps(&self) -> Vec<usize> {
+        self.instructions.values().filter(|i| i.dynamic_jump).map(|i| i.address).collect()
+    }
+
+    /// Whether every jump could be followed, so that the unreachable ranges
+    /// really can't be reached.
+    pub fn unreachable_is_exact(&self) -> bool {
+        !self.instructions.values().any(|i| i.dynamic_jump)
+    }
+}
+
+fn num_parameters(opcode: Number) -> Option<usize> {
+    match opcode {
+        1 | 2 | 7 | 8 => Some(3),
+        5 | 6 => Some(2),
+        3 | 4 | 9 => Some(1),
+        99 => Some(0),
+        _ => None,
+    }
+}
+
+fn write_parameter(opcode: Number) -> Option<usize> {
+    match opcode {
+        1 | 2 | 7 | 8 => Some(2),
+        3 => Some(0),
+        _ => None,
+    }
+}
+
+fn decode(program: &[Number], address: usize, issues: &mut Vec<Issue>) -> Option<Instruction> {
+    let opcode = program[address] % 100;
+    let n = match num_parameters(opcode) {
+        Some(n) => n,
+        None => {
+            issues.push(Issue::InvalidOpcode { address, opcode: program[address] });
+            return None;
+        }
+    };
+    if address + n >= program.len() {
+        issues.push(Issue::Truncated { address });
+        return None;
+    }
+
+    let mut modes = Vec::with_capacity(n);
+    let mut m = program[address] / 100;
+    for parameter in 0..n {
+        let mode = m % 10;
+        m /= 10;
+        if !(0..=2).contains(&mode) || (mode == 1 && write_parameter(opcode) == Some(parameter)) {
+            issues.push(Issue::InvalidMode { address, parameter, mode });
+            return None;
+        }
+        modes.push(mode);
+    }
+    if m != 0 {
+        issues.push(Issue::InvalidMode { address, parameter: n, mode: m % 10 });
+        return None;
+    }
+
+    let mut successors = Vec::new();
+    let mut dynamic_jump = false;
+    match opcode {
+        99 => {}
+        5 | 6 => {
+            let condition = program[address + 1];
+            let always = modes[0] == 1 && ((opcode == 5) == (condition != 0));
+            let never = modes[0] == 1 && !always;
+
+            if !always {
+                successors.push(address + 3);
+            }
+            if !never {
+                let target = program[address + 2];
+                if modes[1] == 1 && target >= 0 && (target as usize) < program.len() {
+                    successors.push(target as usize);
+                } else {
+                    dynamic_jump = true;
+                }
+            }
+        }
+        _ => successors.push(address + n + 1),
+    }
+
+    Some(Instruction {
+        address,
+        opcode,
+        modes,
+        successors,
+        dynamic_jump,
+    })
+}
+
+fn build_blocks(instructions: &BTreeMap<usize, Instruction>) -> Vec<Block> {
+    let mut leaders: BTreeSet<usize> = BTreeSet::new();
+    leaders.insert(0);
+    for i in instructions.values().filter(|i| i.ends_block()) {
+        leaders.extend(i.successors.iter());
+    }
+
+    let mut blocks = Vec::new();
+    for &start in leaders.iter() {
+        let mut current = match instructions.get(&start) {
+            Some(i) => i,
+            None => continue,
+        };
+        loop {
+            let next = current.next_address();
+            if current.ends_block() || leaders.contains(&next) || !instructions.contains_key(&next) {
+                blocks.push(Block {
+                    start,
+                    end: next,
+                    successors: current.successors.clone(),
+                });
+                break;
+            }
+            current = &instructions[&next];
+        }
+    }
+    blocks
+}
+
+/// Decodes every instruction reachable from address 0 without running the
+/// program. Jumps through position or relative mode parameters can't be
+/// followed statically, so they are only recorded as dynamic jumps. Likewise
+/// only position mode writes are checked for self-modification; the target of
+/// a relative mode write depends on the relative base at runtime.
+pub fn analyze(program: &[Number]) -> Analysis {
+    let mut instructions: BTreeMap<usize, Instruction> = BTreeMap::new();
+    let mut issues = Vec::new();
+    let mut visited: HashSet<usize> = HashSet::new();
+    let mut stack = vec![0];
+
+    while let Some(address) = stack.pop() {
+        if address >= program.len() || !visited.insert(address) {
+            continue;
+        }
+        if let Some(instruction) = decode(program, address, &mut issues) {
+            stack.extend(instruction.successors.iter());
+            instructions.insert(address, instruction);
+        }
+    }
+
+    // Cells that were reached but failed to decode still count as code, so
+    // that a write patching them in at runtime gets reported.
+    let mut code = vec![false; program.len()];
+    for address in visited.iter() {
+        code[*address] = true;
+    }
+    for i in instructions.values() {
+        for c in code[i.address..i.next_address()].iter_mut() {
+            *c = true;
+        }
+    }
+
+    for i in instructions.values() {
+        if let Some(parameter) = write_parameter(i.opcode) {
+            let target = program[i.address + parameter + 1];
+            if i.modes[parameter] == 0 && target >= 0 && (target as usize) < program.len() && code[target as usize] {
+                issues.push(Issue::SelfModifyingWrite {
+                    address: i.address,
+                    target: target as usize,
+                });
+            }
+        }
+    }
+    issues.sort_by_key(|issue| match issue {
+        Issue::InvalidOpcode { address, .. }
+        | Issue::InvalidMode { address, .. }
+        | Issue::Truncated { address }
+        | Issue::SelfModifyingWrite { address, .. } => *address,
+    });
+
+    let mut unreachable = Vec::new();
+    let mut start = None;
+    for (address, is_code) in code.iter().enumerate() {
+        match (start, is_code) {
+            (None, false) => start = Some(address),
+            (Some(s), true) => {
+                unreachable.push(s..address);
+                start = None;
+            }
+            _ => {}
+        }
+    }
+    if let Some(s) = start {
+        unreachable.push(s..program.len());
+    }
+
+    Analysis {
+        blocks: build_blocks(&instructions),
+        instructions,
+        issues,
+        unreachable,
+    }
+}
+
+#[test]
+fn test_analyze() {
+    // Jumps over a data cell, then loops on input until it reads a zero.
+    let program = vec![1105, 1, 4, 42, 3, 10, 1005, 10, 4, 99, 0];
+    let analysis = analyze(&program);
+
+    assert_eq!(analysis.instructions.keys().cloned().collect::<Vec<_>>(), vec![0, 4, 6, 9]);
+    assert_eq!(analysis.unreachable, vec![3..4, 10..11]);
+    assert!(analysis.unreachable_is_exact());
+    assert!(analysis.issues.is_empty());
+    assert_eq!(analysis.blocks.len(), 3);
+
+    let program = vec![1, 0, 0, 3, 11102, 1, 1, 0, 98];
+    let analysis = analyze(&program);
+    assert_eq!(
+        analysis.issues,
+        vec![
+            Issue::SelfModifyingWrite { address: 0, target: 3 },
+            Issue::InvalidMode { address: 4, parameter: 2, mode: 1 },
+        ]
+    );
+
+    // Calls a subroutine that returns through the address stored at 100.
+    let program = vec![1101, 0, 7, 100, 1105, 1, 9, 4, 99, 1106, 0, 100];
+    let analysis = analyze(&program);
+    assert_eq!(analysis.dynamic_jumps(), vec![9]);
+    assert_eq!(analysis.unreachable, vec![7..9]);
+    assert!(!analysis.unreachable_is_exact());
+}
@@ -0,0 +1,107 @@
+use std::io;
+use std::io::prelude::*;
+
+use intcode::Number;
+use intcode::Program;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Output {
+    Line(String),
+    Value(Number),
+}
+
+/// Wraps a program that communicates in ASCII: text goes in as character
+/// codes, text comes out as lines, and anything outside the ASCII range is
+/// passed through as a plain value.
+pub struct AsciiProgram {
+    program: Program,
+    partial: String,
+}
+
+impl AsciiProgram {
+    pub fn new(program: Program) -> Self {
+        AsciiProgram {
+            program,
+            partial: String::new(),
+        }
+    }
+
+    pub fn halted(&self) -> bool {
+        self.program.halted()
+    }
+
+    pub fn push_str(&mut self, s: &str) {
+        for b in s.bytes() {
+            self.program.push_input(b as Number);
+        }
+    }
+
+    pub fn push_line(&mut self, line: &str) {
+        self.push_str(line);
+        self.program.push_input(b'\n' as Number);
+    }
+
+    /// Runs until the program halts or needs input. A line that has not been
+    /// terminated by then (usually a prompt) is returned as well.
+    pub fn run_till_halted_or_blocked(&mut self) -> Vec<Output> {
+        let mut output = Vec::new();
+
+        self.program.run_till_halted_or_blocked();
+        while let Some(n) = self.program.get_output() {
+            if n == b'\n' as Number {
+                output.push(Output::Line(std::mem::take(&mut self.partial)));
+            } else if (0..128).contains(&n) {
+                self.partial.push(n as u8 as char);
+            } else {
+                if !self.partial.is_empty() {
+                    output.push(Output::Line(std::mem::take(&mut self.partial)));
+                }
+                output.push(Output::Value(n));
+            }
+        }
+        if !self.partial.is_empty() {
+            output.push(Output::Line(std::mem::take(&mut self.partial)));
+        }
+
+        output
+    }
+
+    pub fn run_session<R: BufRead, W: Write>(&mut self, mut input: R, mut output: W) -> io::Result<()> {
+        loop {
+            for o in self.run_till_halted_or_blocked() {
+                match o {
+                    Output::Line(s) => writeln!(output, "{}", s)?,
+                    Output::Value(n) => writeln!(output, "[{}]", n)?,
+                }
+            }
+            output.flush()?;
+
+            if self.halted() {
+                break;
+            }
+
+            let mut line = String::new();
+            if input.read_line(&mut line)? == 0 {
+                break;
+            }
+            self.push_line(line.trim_end_matches(&['\r', '\n'][..]));
+        }
+        Ok(())
+    }
+}
+
+#[test]
+fn test_echo() {
+    let mut p = AsciiProgram::new(Program::new(vec![3, 100, 4, 100, 1105, 1, 0]));
+    assert_eq!(p.run_till_halted_or_blocked(), vec![]);
+
+    p.push_line("hi");
+    assert_eq!(p.run_till_halted_or_blocked(), vec![Output::Line(String::from("hi"))]);
+
+    p.push_str("a");
+    p.program.push_input(1000);
+    assert_eq!(
+        p.run_till_halted_or_blocked(),
+        vec![Output::Line(String::from("a")), Output::Value(1000)]
+    );
+}
@@ -0,0 +1,25 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use intcode::Number;
+use intcode::Program;
+
+use adventofcode_rust_2019::ascii::AsciiProgram;
+
+fn main() -> io::Result<()> {
+    let filename = match env::args().nth(1) {
+        Some(f) => f,
+        None => {
+            eprintln!("Usage: ascii <PROGRAM_FILE>");
+            std::process::exit(1);
+        }
+    };
+
+    let input = fs::read_to_string(filename)?;
+    let input = input.split('\n').next().unwrap();
+    let input: Vec<Number> = input.split(',').map(|s| s.parse::<Number>().unwrap()).collect();
+
+    let mut program = AsciiProgram::new(Program::new(input));
+    program.run_session(io::stdin().lock(), io::stdout().lock())
+}
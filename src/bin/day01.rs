@@ -1,38 +1,204 @@
+use std::env;
 use std::io;
 use std::io::prelude::*;
 
-fn get_fuel(mass: i32) -> i32 {
-    mass / 3 - 2
+type Mass = u128;
+
+fn get_fuel(mass: Mass) -> Mass {
+    (mass / 3).saturating_sub(2)
 }
 
-fn get_fuel_part_2(mass: i32) -> i32 {
-    let mut total = 0;
-    let mut subtotal = mass;
-    loop {
+/// The fuel for the module, then the fuel for that fuel, and so on.
+fn get_fuel_chain(mass: Mass) -> Vec<Mass> {
+    let mut chain = Vec::new();
+    let mut subtotal = get_fuel(mass);
+    while subtotal > 0 {
+        chain.push(subtotal);
         subtotal = get_fuel(subtotal);
-        if subtotal <= 0 {
-            break;
+    }
+    chain
+}
+
+fn get_fuel_part_2(mass: Mass) -> Mass {
+    get_fuel_chain(mass).iter().sum()
+}
+
+const TABLE_SIZE: usize = 1 << 20;
+
+/// Recursive fuel for every mass below the table size. Larger masses are
+/// brought down with a few applications of `get_fuel` first, which shrinks
+/// them by a factor of three each time, so the table never needs to be larger
+/// than the heaviest mass asked about.
+struct FuelTable {
+    table: Vec<u32>,
+}
+
+impl FuelTable {
+    fn new(size: usize) -> Self {
+        let mut table: Vec<u32> = Vec::with_capacity(size);
+        for mass in 0..size {
+            let fuel = get_fuel(mass as Mass) as usize;
+            let total = if fuel == 0 { 0 } else { fuel as u32 + table[fuel] };
+            table.push(total);
+        }
+        FuelTable { table }
+    }
+
+    fn get(&self, mass: Mass) -> Mass {
+        let mut total = 0;
+        let mut subtotal = mass;
+        while subtotal >= self.table.len() as Mass {
+            subtotal = get_fuel(subtotal);
+            total += subtotal;
+        }
+        total + self.table[subtotal as usize] as Mass
+    }
+}
+
+#[test]
+fn test_fuel_table() {
+    // The original loop, on signed masses where fuel can drop below zero.
+    let reference = |mass: Mass| -> Mass {
+        let mut total = 0;
+        let mut subtotal = mass as i128;
+        loop {
+            subtotal = subtotal / 3 - 2;
+            if subtotal <= 0 {
+                break;
+            }
+            total += subtotal;
+        }
+        total as Mass
+    };
+
+    let table = FuelTable::new(1000);
+    for mass in 0..100_000 {
+        assert_eq!(table.get(mass), reference(mass));
+    }
+
+    let mut mass: Mass = 1;
+    while let Some(m) = mass.checked_mul(7).filter(|m| *m <= i128::MAX as Mass) {
+        mass = m;
+        assert_eq!(table.get(mass), reference(mass));
+        assert_eq!(table.get(mass - 1), reference(mass - 1));
+    }
+    assert_eq!(table.get(Mass::MAX), get_fuel_part_2(Mass::MAX));
+}
+
+struct FuelBreakdown {
+    mass: Mass,
+    direct: Mass,
+    recursive: Mass,
+    chain: Vec<Mass>,
+}
+
+impl FuelBreakdown {
+    fn new(mass: Mass) -> Self {
+        let chain = get_fuel_chain(mass);
+        FuelBreakdown {
+            mass,
+            direct: get_fuel(mass),
+            recursive: get_fuel_part_2(mass),
+            chain,
+        }
+    }
+}
+
+/// Finds the largest mass for which `fuel` stays within the budget, relying
+/// on both fuel functions never decreasing as the mass grows.
+fn get_max_mass<F: Fn(Mass) -> Mass>(budget: Mass, fuel: F) -> Mass {
+    // Anything heavier than this needs more direct fuel than the budget, and
+    // recursive fuel is never less than direct fuel.
+    let mut max = match budget.checked_mul(3).and_then(|m| m.checked_add(8)) {
+        Some(m) => m,
+        None => Mass::MAX,
+    };
+    let mut min = 0;
+
+    while min < max {
+        let pivot = max - (max - min) / 2;
+        if fuel(pivot) <= budget {
+            min = pivot;
+        } else {
+            max = pivot - 1;
         }
-        total += subtotal;
     }
-    total
+    min
+}
+
+#[test]
+fn test_max_mass() {
+    let table = FuelTable::new(1000);
+    let recursive = |m| table.get(m);
+    for budget in [0, 1, 2, 5, 100, 654, 966, 50_346, 1 << 40, Mass::MAX / 3, Mass::MAX] {
+        let mass = get_max_mass(budget, get_fuel);
+        assert!(get_fuel(mass) <= budget);
+        assert!(mass == Mass::MAX || get_fuel(mass + 1) > budget);
+
+        let mass = get_max_mass(budget, recursive);
+        assert!(recursive(mass) <= budget);
+        assert!(mass == Mass::MAX || recursive(mass + 1) > budget);
+    }
+    assert_eq!(get_max_mass(0, get_fuel), 8);
+    assert_eq!(get_max_mass(Mass::MAX, get_fuel), Mass::MAX);
+}
+
+#[test]
+fn test_fuel_breakdown() {
+    let b = FuelBreakdown::new(1969);
+    assert_eq!(b.direct, 654);
+    assert_eq!(b.recursive, 966);
+    assert_eq!(b.chain, vec![654, 216, 70, 21, 5]);
+
+    let b = FuelBreakdown::new(8);
+    assert_eq!((b.direct, b.recursive), (0, 0));
+    assert!(b.chain.is_empty());
 }
 
 fn main() -> io::Result<()> {
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
 
-    let input: Vec<i32> = input
+    let input: Vec<Mass> = input
         .split("\n")
         .filter(|l| *l != "")
-        .map(|s| s.parse::<i32>().unwrap())
+        .map(|s| s.parse::<Mass>().unwrap())
         .collect();
 
-    let sum: i32 = input.iter().map(|i| get_fuel(*i)).sum();
+    let sum: Mass = input.iter().map(|i| get_fuel(*i)).sum();
     println!("The fuel required: {}", sum);
 
-    let sum: i32 = input.iter().map(|i| get_fuel_part_2(*i)).sum();
+    let heaviest = input.iter().copied().max().unwrap_or(0);
+    let table = FuelTable::new(heaviest.saturating_add(1).min(TABLE_SIZE as Mass) as usize);
+    let sum: Mass = input.iter().map(|i| table.get(*i)).sum();
     println!("The fuel required, taking into account fuel mass: {}", sum);
 
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--breakdown" => {
+                println!("{:>12} {:>12} {:>12}  chain", "mass", "direct", "recursive");
+                for b in input.iter().map(|i| FuelBreakdown::new(*i)) {
+                    let chain: Vec<String> = b.chain.iter().map(|f| f.to_string()).collect();
+                    println!("{:>12} {:>12} {:>12}  {}", b.mass, b.direct, b.recursive, chain.join(" -> "));
+                }
+            }
+            "--max-mass" => {
+                let budget: Mass = args.next().expect("--max-mass needs a fuel budget").parse().unwrap();
+                println!("The heaviest module within a fuel budget of {}: {}", budget, get_max_mass(budget, get_fuel));
+                println!(
+                    "The heaviest module within a fuel budget of {}, taking into account fuel mass: {}",
+                    budget,
+                    get_max_mass(budget, |m| table.get(m))
+                );
+            }
+            _ => {
+                eprintln!("Usage: day01 [--breakdown] [--max-mass <FUEL>] < INPUT");
+                std::process::exit(1);
+            }
+        }
+    }
+
     Ok(())
 }
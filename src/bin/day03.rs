@@ -1,5 +1,10 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::time::Instant;
 
 use nom::{
     branch::alt,
@@ -10,6 +15,8 @@ use nom::{
     IResult,
 };
 
+use adventofcode_rust_2019::rng::Rng;
+
 #[derive(Clone)]
 enum Direction {
     Up,
@@ -51,9 +58,8 @@ impl Segment {
         }
     }
 
-    fn contains(&self, point: &(i32, i32)) -> bool {
+    fn bounds(&self) -> (i32, i32, i32, i32) {
         let (dx, dy) = self.delta();
-        let (p, q) = point;
         let minx;
         let maxx;
         let miny;
@@ -74,8 +80,19 @@ impl Segment {
             maxy = self.y + dy;
         }
 
+        (minx, maxx, miny, maxy)
+    }
+
+    fn contains(&self, point: &(i32, i32)) -> bool {
+        let (minx, maxx, miny, maxy) = self.bounds();
+        let (p, q) = point;
+
         *p >= minx && *p <= maxx && *q >= miny && *q <= maxy
     }
+
+    fn steps_to(&self, point: &(i32, i32)) -> i32 {
+        self.steps + (point.0 - self.x).abs() + (point.1 - self.y).abs()
+    }
 }
 
 fn parse_direction_up(input: &str) -> IResult<&str, Direction> {
@@ -127,7 +144,7 @@ fn parse_wires(input: &str) -> IResult<&str, Vec<Vec<Segment>>> {
     separated_list1(char('\n'), parse_wire)(input)
 }
 
-fn get_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32)> {
+fn get_intersections_brute_force(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32, i32)> {
     let mut intersections = Vec::new();
     for a in v1.iter() {
         for b in v2.iter() {
@@ -136,7 +153,7 @@ fn get_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32
                 if a.contains(&intersection) && b.contains(&intersection) {
                     let steps_a = a.steps + (b.x - a.x).abs();
                     let steps_b = b.steps + (b.y - a.y).abs();
-                    intersections.push((intersection.0, intersection.1, steps_a + steps_b));
+                    intersections.push((intersection.0, intersection.1, steps_a, steps_b));
                 }
             }
             if a.dir.vertical() && b.dir.horizontal() {
@@ -144,7 +161,7 @@ fn get_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32
                 if a.contains(&intersection) && b.contains(&intersection) {
                     let steps_a = a.steps + (b.y - a.y).abs();
                     let steps_b = b.steps + (b.x - a.x).abs();
-                    intersections.push((intersection.0, intersection.1, steps_a + steps_b));
+                    intersections.push((intersection.0, intersection.1, steps_a, steps_b));
                 }
             }
         }
@@ -152,6 +169,355 @@ fn get_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32
     intersections
 }
 
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum EventKind {
+    Insert,
+    Query,
+    Remove,
+}
+
+/// Finds all points shared by two wires: where a horizontal segment of one
+/// crosses a vertical segment of the other, and where segments overlap.
+fn get_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32, i32)> {
+    let mut intersections = get_perpendicular_intersections(v1, v2);
+    intersections.extend(get_overlaps(v1, v2, true));
+    intersections.extend(get_overlaps(v1, v2, false));
+    intersections
+}
+
+/// Finds all crossings between horizontal and vertical segments of two wires
+/// with a sweep from left to right. Horizontal segments are kept in an active
+/// set ordered by height while the sweep is within them, so every vertical
+/// segment only has to look at the horizontal segments of the other wire that
+/// it actually crosses. This finds the same points as the brute force search.
+fn get_perpendicular_intersections(v1: &Vec<Segment>, v2: &Vec<Segment>) -> Vec<(i32, i32, i32, i32)> {
+    let wires = [v1, v2];
+    let mut events = Vec::new();
+    for (w, wire) in wires.iter().enumerate() {
+        for (i, segment) in wire.iter().enumerate() {
+            let (minx, maxx, _miny, _maxy) = segment.bounds();
+            if segment.dir.horizontal() {
+                events.push((minx, EventKind::Insert, w, i));
+                events.push((maxx, EventKind::Remove, w, i));
+            } else {
+                events.push((minx, EventKind::Query, w, i));
+            }
+        }
+    }
+    events.sort_unstable();
+
+    let mut intersections = Vec::new();
+    let mut active: [BTreeSet<(i32, usize)>; 2] = [BTreeSet::new(), BTreeSet::new()];
+    for (x, kind, w, i) in events {
+        let segment = &wires[w][i];
+        match kind {
+            EventKind::Insert => {
+                active[w].insert((segment.y, i));
+            }
+            EventKind::Remove => {
+                active[w].remove(&(segment.y, i));
+            }
+            EventKind::Query => {
+                let (_minx, _maxx, miny, maxy) = segment.bounds();
+                for &(y, j) in active[1 - w].range((miny, 0)..=(maxy, usize::MAX)) {
+                    let other = &wires[1 - w][j];
+                    let point = (x, y);
+                    let (steps_v, steps_h) = (segment.steps_to(&point), other.steps_to(&point));
+                    if w == 0 {
+                        intersections.push((x, y, steps_v, steps_h));
+                    } else {
+                        intersections.push((x, y, steps_h, steps_v));
+                    }
+                }
+            }
+        }
+    }
+    intersections
+}
+
+/// Finds every point shared by two segments that lie on the same line. On
+/// each line the segments are swept by their start, and a segment overlaps
+/// with exactly those segments of the other wire that have started but not
+/// yet ended.
+fn get_overlaps(v1: &Vec<Segment>, v2: &Vec<Segment>, horizontal: bool) -> Vec<(i32, i32, i32, i32)> {
+    // (line, start, end, wire, index), with start and end along the line
+    let mut intervals = Vec::new();
+    for (w, wire) in [v1, v2].iter().enumerate() {
+        for (i, segment) in wire.iter().enumerate() {
+            let (minx, maxx, miny, maxy) = segment.bounds();
+            if segment.dir.horizontal() == horizontal {
+                if horizontal {
+                    intervals.push((miny, minx, maxx, w, i));
+                } else {
+                    intervals.push((minx, miny, maxy, w, i));
+                }
+            }
+        }
+    }
+    intervals.sort_unstable();
+
+    let mut overlaps = Vec::new();
+    let mut line = None;
+    let mut active: [BTreeSet<(i32, usize)>; 2] = [BTreeSet::new(), BTreeSet::new()];
+    for (l, start, end, w, i) in intervals {
+        if line != Some(l) {
+            line = Some(l);
+            active = [BTreeSet::new(), BTreeSet::new()];
+        }
+        while let Some(&(e, j)) = active[1 - w].iter().next() {
+            if e >= start {
+                break;
+            }
+            active[1 - w].remove(&(e, j));
+        }
+        for &(e, j) in active[1 - w].iter() {
+            let (a, b) = if w == 0 { (&v1[i], &v2[j]) } else { (&v1[j], &v2[i]) };
+            for p in start..=end.min(e) {
+                let point = if horizontal { (p, l) } else { (l, p) };
+                overlaps.push((point.0, point.1, a.steps_to(&point), b.steps_to(&point)));
+            }
+        }
+        active[w].insert((end, i));
+    }
+    overlaps
+}
+
+#[test]
+fn test_intersections_against_grid() {
+    let trace = |wire: &Vec<Segment>| {
+        let mut points: HashMap<(i32, i32), i32> = HashMap::new();
+        for segment in wire.iter() {
+            let (dx, dy) = segment.delta();
+            for d in 0..=segment.dist {
+                let point = (segment.x + dx.signum() * d, segment.y + dy.signum() * d);
+                points.entry(point).or_insert(segment.steps + d);
+            }
+        }
+        points
+    };
+
+    for seed in 1..50 {
+        let v1 = generate_wire(seed, 40, 6);
+        let v2 = generate_wire(seed + 1000, 40, 6);
+        let (p1, p2) = (trace(&v1), trace(&v2));
+
+        let mut expected: Vec<(i32, i32)> = p1.keys().filter(|p| p2.contains_key(p)).cloned().collect();
+        let mut found: Vec<(i32, i32)> = get_intersections(&v1, &v2).iter().map(|t| (t.0, t.1)).collect();
+        expected.sort();
+        found.sort();
+        found.dedup();
+        assert_eq!(found, expected);
+
+        let mut brute_force = get_intersections_brute_force(&v1, &v2);
+        let mut perpendicular = get_perpendicular_intersections(&v1, &v2);
+        brute_force.sort_unstable();
+        perpendicular.sort_unstable();
+        assert_eq!(perpendicular, brute_force);
+    }
+}
+
+/// A point where two or more wires cross, with the number of steps each of
+/// those wires takes to get there.
+struct Crossing {
+    x: i32,
+    y: i32,
+    steps: BTreeMap<usize, i32>,
+}
+
+impl Crossing {
+    fn distance(&self) -> i32 {
+        self.x.abs() + self.y.abs()
+    }
+
+    fn contains_all(&self, wires: &[usize]) -> bool {
+        wires.iter().all(|w| self.steps.contains_key(w))
+    }
+
+    fn combined_steps(&self, wires: &[usize]) -> i32 {
+        wires.iter().map(|w| self.steps[w]).sum()
+    }
+}
+
+fn get_crossings(wires: &[Vec<Segment>]) -> Vec<Crossing> {
+    let mut crossings: HashMap<(i32, i32), BTreeMap<usize, i32>> = HashMap::new();
+
+    for i in 0..wires.len() {
+        for j in i + 1..wires.len() {
+            for (x, y, steps_i, steps_j) in get_intersections(&wires[i], &wires[j]) {
+                if x == 0 && y == 0 {
+                    continue;
+                }
+                // A wire can pass through the same point more than once, in
+                // which case only its first visit counts.
+                let steps = crossings.entry((x, y)).or_default();
+                let s = steps.entry(i).or_insert(steps_i);
+                *s = (*s).min(steps_i);
+                let s = steps.entry(j).or_insert(steps_j);
+                *s = (*s).min(steps_j);
+            }
+        }
+    }
+
+    let mut crossings: Vec<Crossing> = crossings
+        .into_iter()
+        .map(|((x, y), steps)| Crossing { x, y, steps })
+        .collect();
+    crossings.sort_by_key(|c| (c.distance(), c.x, c.y));
+    crossings
+}
+
+/// A stretch of wire that leaves a point and later comes back to it.
+struct Loop {
+    x: i32,
+    y: i32,
+    start: i32,
+    end: i32,
+}
+
+/// Finds all loops in a wire by crossing it with itself. Every pair of
+/// segments through a point shows up here, including a segment paired with
+/// itself and the corners between consecutive segments, so only pairs
+/// reaching the point at different step counts are actual revisits.
+fn get_loops(wire: &Vec<Segment>) -> Vec<Loop> {
+    let mut visits: HashMap<(i32, i32), Vec<i32>> = HashMap::new();
+    for (x, y, steps_a, steps_b) in get_intersections(wire, wire) {
+        if steps_a < steps_b {
+            let v = visits.entry((x, y)).or_default();
+            v.push(steps_a);
+            v.push(steps_b);
+        }
+    }
+
+    let mut loops = Vec::new();
+    for ((x, y), mut steps) in visits {
+        steps.sort_unstable();
+        steps.dedup();
+        for w in steps.windows(2) {
+            loops.push(Loop { x, y, start: w[0], end: w[1] });
+        }
+    }
+    loops.sort_by_key(|l| (l.start, l.end));
+    loops
+}
+
+#[test]
+fn test_loops_and_revisits() {
+    let wire = |s: &str| {
+        let (_rest, mut wire) = parse_wire(s).unwrap();
+        fill_coordinates_and_steps(&mut wire);
+        wire
+    };
+    let a = wire("R5,U2,L2,D4");
+    let b = wire("D1,R3,U3");
+
+    let loops = get_loops(&a);
+    assert_eq!(loops.len(), 1);
+    assert_eq!((loops[0].x, loops[0].y, loops[0].start, loops[0].end), (3, 0, 3, 11));
+
+    let crossings = get_crossings(&[a, b]);
+    let c = crossings.iter().find(|c| (c.x, c.y) == (3, 0)).unwrap();
+    assert_eq!(c.steps, BTreeMap::from([(0, 3), (1, 5)]));
+}
+
+fn get_closest<'a>(crossings: &'a [Crossing], wires: &[usize]) -> Option<&'a Crossing> {
+    crossings.iter().filter(|c| c.contains_all(wires)).min_by_key(|c| c.distance())
+}
+
+fn get_fewest_steps<'a>(crossings: &'a [Crossing], wires: &[usize]) -> Option<&'a Crossing> {
+    crossings.iter().filter(|c| c.contains_all(wires)).min_by_key(|c| c.combined_steps(wires))
+}
+
+const WIRE_COLORS: [&str; 6] = ["#1f77b4", "#d62728", "#2ca02c", "#9467bd", "#ff7f0e", "#17becf"];
+
+/// Draws the wires with the y axis pointing up, like in the puzzle. All
+/// sizes are relative to the extent of the wires, so small examples and real
+/// inputs both come out readable.
+fn render_svg(wires: &[Vec<Segment>], crossings: &[Crossing], selected: &[usize]) -> String {
+    let mut points: Vec<Vec<(i32, i32)>> = Vec::new();
+    for wire in wires.iter() {
+        let mut p = vec![(0, 0)];
+        for segment in wire.iter() {
+            let (dx, dy) = segment.delta();
+            p.push((segment.x + dx, segment.y + dy));
+        }
+        points.push(p);
+    }
+
+    let all = points.iter().flatten();
+    let minx = all.clone().map(|p| p.0).min().unwrap_or(0);
+    let maxx = all.clone().map(|p| p.0).max().unwrap_or(0);
+    let miny = all.clone().map(|p| p.1).min().unwrap_or(0);
+    let maxy = all.map(|p| p.1).max().unwrap_or(0);
+    let size = ((maxx - minx).max(maxy - miny).max(1)) as f64;
+    let margin = size / 20.0;
+    let stroke = size / 300.0;
+
+    let view_box = format!(
+        "{} {} {} {}",
+        minx as f64 - margin,
+        -maxy as f64 - margin,
+        (maxx - minx) as f64 + 2.0 * margin,
+        (maxy - miny) as f64 + 2.0 * margin
+    );
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{}">"#, view_box).unwrap();
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="100%" height="100%" fill="white"/>"#,
+        minx as f64 - margin,
+        -maxy as f64 - margin
+    )
+    .unwrap();
+
+    for (w, p) in points.iter().enumerate() {
+        let p: Vec<String> = p.iter().map(|(x, y)| format!("{},{}", x, -y)).collect();
+        writeln!(
+            svg,
+            r#"<polyline points="{}" fill="none" stroke="{}" stroke-width="{}" stroke-opacity="0.8"/>"#,
+            p.join(" "),
+            WIRE_COLORS[w % WIRE_COLORS.len()],
+            stroke
+        )
+        .unwrap();
+    }
+
+    for c in crossings.iter() {
+        writeln!(svg, r#"<circle cx="{}" cy="{}" r="{}" fill="black"/>"#, c.x, -c.y, stroke * 2.0).unwrap();
+    }
+
+    let highlights = [
+        (get_closest(crossings, selected), "gold"),
+        (get_fewest_steps(crossings, selected), "magenta"),
+    ];
+    for (c, color) in highlights.iter() {
+        if let Some(c) = c {
+            writeln!(
+                svg,
+                r#"<circle cx="{}" cy="{}" r="{}" fill="none" stroke="{}" stroke-width="{}"/>"#,
+                c.x,
+                -c.y,
+                stroke * 8.0,
+                color,
+                stroke * 2.0
+            )
+            .unwrap();
+        }
+    }
+
+    writeln!(
+        svg,
+        r#"<rect x="{}" y="{}" width="{}" height="{}" fill="black"/>"#,
+        -stroke * 4.0,
+        -stroke * 4.0,
+        stroke * 8.0,
+        stroke * 8.0
+    )
+    .unwrap();
+    svg.push_str("</svg>\n");
+    svg
+}
+
 fn fill_coordinates_and_steps(v: &mut Vec<Segment>) {
     let mut x = 0;
     let mut y = 0;
@@ -170,28 +536,116 @@ fn fill_coordinates_and_steps(v: &mut Vec<Segment>) {
     }
 }
 
+/// Generates a random wire for benchmarking and testing.
+fn generate_wire(seed: u64, num_segments: usize, max_dist: i32) -> Vec<Segment> {
+    let mut rng = Rng::new(seed);
+    let mut wire = Vec::with_capacity(num_segments);
+    for _ in 0..num_segments {
+        let dir = [Direction::Up, Direction::Down, Direction::Left, Direction::Right][rng.below(4)].clone();
+        let dist = rng.range(1, max_dist as i64) as i32;
+        wire.push(Segment { dir, dist, x: 0, y: 0, steps: 0 });
+    }
+    fill_coordinates_and_steps(&mut wire);
+    wire
+}
+
+fn benchmark(num_segments: usize) {
+    let v1 = generate_wire(0x2545f4914f6cdd1d, num_segments, 1000);
+    let v2 = generate_wire(0x9e3779b97f4a7c15, num_segments, 1000);
+
+    let start = Instant::now();
+    let mut sweep = get_perpendicular_intersections(&v1, &v2);
+    println!("Sweep line: {} crossings in {:?}", sweep.len(), start.elapsed());
+
+    let start = Instant::now();
+    let mut brute_force = get_intersections_brute_force(&v1, &v2);
+    println!("Brute force: {} crossings in {:?}", brute_force.len(), start.elapsed());
+
+    sweep.sort_unstable();
+    brute_force.sort_unstable();
+    if sweep != brute_force {
+        println!("The sweep line and brute force crossings differ");
+    }
+
+    let start = Instant::now();
+    let count = get_overlaps(&v1, &v2, true).len() + get_overlaps(&v1, &v2, false).len();
+    println!("Overlapping segments, not found by brute force: {} points in {:?}", count, start.elapsed());
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: day03 [--wires <N>,<N>,...] [--list] [--loops] [--svg <FILE>] < INPUT");
+    eprintln!("       day03 --bench <NUM_SEGMENTS>");
+    std::process::exit(1);
+}
+
 fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() == 2 && args[0] == "--bench" {
+        benchmark(args[1].parse().unwrap_or_else(|_| usage()));
+        return Ok(());
+    }
+
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
     let input = &input[..];
     let (_rest, mut input) = parse_wires(input).unwrap();
 
-    fill_coordinates_and_steps(&mut input[0]);
-    fill_coordinates_and_steps(&mut input[1]);
+    for wire in input.iter_mut() {
+        fill_coordinates_and_steps(wire);
+    }
+    let crossings = get_crossings(&input);
+
+    let mut selected: Vec<usize> = (0..input.len()).collect();
+    let mut list = false;
+    let mut loops = false;
+    let mut svg = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--wires" => {
+                let wires = args.next().unwrap_or_else(|| usage());
+                selected = wires.split(',').map(|s| s.parse::<usize>().unwrap_or_else(|_| usage())).collect();
+                if selected.iter().any(|w| *w >= input.len()) {
+                    eprintln!("There are only {} wires", input.len());
+                    std::process::exit(1);
+                }
+            }
+            "--list" => list = true,
+            "--loops" => loops = true,
+            "--svg" => svg = Some(args.next().unwrap_or_else(|| usage())),
+            _ => usage(),
+        }
+    }
 
-    let min_distance: i32 = get_intersections(&input[0], &input[1])
-        .iter()
-        .map(|(x, y, _s)| x.abs() + y.abs())
-        .min()
-        .unwrap();
-    let min_steps: i32 = get_intersections(&input[0], &input[1])
-        .iter()
-        .map(|(_x, _y, s)| *s)
-        .min()
-        .unwrap();
+    if loops {
+        for (w, wire) in input.iter().enumerate() {
+            let wire_loops = get_loops(wire);
+            let num_points = wire_loops.iter().map(|l| (l.x, l.y)).collect::<BTreeSet<_>>().len();
+            println!("Wire {} crosses itself at {} points and has {} loops", w, num_points, wire_loops.len());
+            for l in wire_loops.iter() {
+                println!("    ({}, {}) from step {} to step {}, length {}", l.x, l.y, l.start, l.end, l.end - l.start);
+            }
+        }
+    }
+
+    if list {
+        for c in crossings.iter() {
+            let steps: Vec<String> = c.steps.iter().map(|(w, s)| format!("wire {}: {}", w, s)).collect();
+            println!("({}, {}) distance {}, {}", c.x, c.y, c.distance(), steps.join(", "));
+        }
+    }
 
-    println!("The distance of the closest intersection is: {}", min_distance);
-    println!("The minimum combined number of steps: {}", min_steps);
+    if let Some(filename) = svg {
+        fs::write(filename, render_svg(&input, &crossings, &selected))?;
+    }
+
+    match get_closest(&crossings, &selected) {
+        Some(c) => println!("The distance of the closest intersection is: {}", c.distance()),
+        None => println!("The selected wires do not all cross at any point"),
+    }
+    if let Some(c) = get_fewest_steps(&crossings, &selected) {
+        println!("The minimum combined number of steps: {}", c.combined_steps(&selected));
+    }
 
     Ok(())
 }
@@ -1,7 +1,18 @@
+use std::collections::HashMap;
+use std::env;
 use std::io;
 use std::io::prelude::*;
 
-fn digits(mut n: u32) -> Vec<u32> {
+use nom::{
+    branch::alt,
+    character::complete::{char, digit1, multispace1, not_line_ending, space0},
+    combinator::{map_res, recognize, value},
+    multi::many0,
+    sequence::{pair, separated_pair, terminated, tuple},
+    IResult,
+};
+
+fn digits(mut n: u64) -> Vec<u64> {
     let mut v = Vec::with_capacity(6);
     while n > 0 {
         v.push(n % 10);
@@ -10,7 +21,7 @@ fn digits(mut n: u32) -> Vec<u32> {
     v
 }
 
-fn valid_password(n: u32) -> bool {
+fn valid_password(n: u64) -> bool {
     let mut prev = None;
     let mut found_equal = false;
 
@@ -30,7 +41,7 @@ fn valid_password(n: u32) -> bool {
     found_equal
 }
 
-fn password_contains_pair(n: u32) -> bool {
+fn password_contains_pair(n: u64) -> bool {
     let mut prev = None;
     let mut equal_count = 1;
 
@@ -50,19 +61,507 @@ fn password_contains_pair(n: u32) -> bool {
     equal_count == 2
 }
 
+#[derive(Clone, Debug, PartialEq, Eq)]
+enum Rule {
+    NonDecreasing,
+    NonIncreasing,
+    /// At least one run of equal digits of at least this length.
+    MinRun(u8),
+    /// No run of equal digits longer than this.
+    MaxRun(u8),
+    /// At least one run of equal digits of exactly this length.
+    ExactRun(u8),
+    Length(u32),
+    /// Bit `d` is set if digit `d` may be used.
+    AllowedDigits(u16),
+}
+
+#[derive(Clone, Debug)]
+struct RuleSet {
+    rules: Vec<Rule>,
+}
+
+impl RuleSet {
+    fn new(rules: Vec<Rule>) -> Self {
+        RuleSet { rules }
+    }
+
+    fn part1() -> Self {
+        RuleSet::new(vec![Rule::NonDecreasing, Rule::MinRun(2)])
+    }
+
+    fn part2() -> Self {
+        RuleSet::new(vec![Rule::NonDecreasing, Rule::ExactRun(2)])
+    }
+
+    fn matches(&self, n: u64) -> bool {
+        let digits: Vec<u8> = n.to_string().bytes().map(|b| b - b'0').collect();
+        let mut runs: Vec<usize> = Vec::new();
+        let mut run = 1;
+        for (i, d) in digits.iter().enumerate() {
+            if i + 1 < digits.len() && digits[i + 1] == *d {
+                run += 1;
+            } else {
+                runs.push(run);
+                run = 1;
+            }
+        }
+
+        self.rules.iter().all(|rule| match rule {
+            Rule::NonDecreasing => digits.windows(2).all(|w| w[0] <= w[1]),
+            Rule::NonIncreasing => digits.windows(2).all(|w| w[0] >= w[1]),
+            Rule::MinRun(k) => runs.iter().any(|r| *r >= *k as usize),
+            Rule::MaxRun(k) => runs.iter().all(|r| *r <= *k as usize),
+            Rule::ExactRun(k) => runs.contains(&(*k as usize)),
+            Rule::Length(l) => digits.len() == *l as usize,
+            Rule::AllowedDigits(mask) => digits.iter().all(|d| mask & (1 << d) != 0),
+        })
+    }
+
+    /// Narrows a range down to the numbers that have the required length.
+    fn bounds(&self, mut a: u64, mut b: u64) -> Option<(u64, u64)> {
+        for rule in self.rules.iter() {
+            if let Rule::Length(l) = rule {
+                let min = if *l <= 1 { 0 } else { 10u64.checked_pow(l - 1)? };
+                let max = 10u64.checked_pow(*l).map(|m| m - 1).unwrap_or(u64::MAX);
+                a = a.max(min);
+                b = b.min(max);
+            }
+        }
+        if a <= b {
+            Some((a, b))
+        } else {
+            None
+        }
+    }
+
+    fn count(&self, a: u64, b: u64) -> u64 {
+        match self.bounds(a, b) {
+            Some((a, b)) => DigitCounter::new(self, a, b).count(0, true, true, None, 0, 0),
+            None => 0,
+        }
+    }
+
+    fn enumerate<F: FnMut(u64)>(&self, a: u64, b: u64, mut f: F) {
+        if let Some((a, b)) = self.bounds(a, b) {
+            DigitCounter::new(self, a, b).enumerate(0, true, true, None, 0, 0, 0, &mut f);
+        }
+    }
+}
+
+type State = (usize, bool, bool, Option<u8>, u8, u32);
+
+/// Counts passwords in a range by choosing digits from the most significant
+/// one down, keeping track of whether the digits so far are still equal to
+/// those of either end of the range. Beyond that, all that matters is the
+/// last digit, the length of the run it ends (capped just above the longest
+/// run any rule cares about), and which of the goals have been met already.
+/// The goals are the runs that have to appear somewhere: the longest minimum
+/// run, and every distinct exact run that fits in the number of digits. That
+/// keeps them well within the bits of `found`, however many rules there are.
+struct DigitCounter<'a> {
+    rules: &'a RuleSet,
+    goals: Vec<Rule>,
+    impossible: bool,
+    low: Vec<u8>,
+    high: Vec<u8>,
+    run_cap: u8,
+    memo: HashMap<State, u64>,
+}
+
+impl<'a> DigitCounter<'a> {
+    fn new(rules: &'a RuleSet, a: u64, b: u64) -> Self {
+        let high: Vec<u8> = b.to_string().bytes().map(|b| b - b'0').collect();
+        let mut low: Vec<u8> = a.to_string().bytes().map(|b| b - b'0').collect();
+        while low.len() < high.len() {
+            low.insert(0, 0);
+        }
+
+        let run_cap = rules
+            .rules
+            .iter()
+            .filter_map(|r| match r {
+                Rule::MinRun(k) | Rule::MaxRun(k) | Rule::ExactRun(k) => Some(k.saturating_add(1)),
+                _ => None,
+            })
+            .max()
+            .unwrap_or(1);
+
+        let mut goals: Vec<Rule> = Vec::new();
+        let min_runs = rules.rules.iter().filter_map(|r| match r {
+            Rule::MinRun(k) => Some(*k),
+            _ => None,
+        });
+        if let Some(k) = min_runs.max() {
+            goals.push(Rule::MinRun(k));
+        }
+        let mut exact_runs: Vec<u8> = rules
+            .rules
+            .iter()
+            .filter_map(|r| match r {
+                Rule::ExactRun(k) => Some(*k),
+                _ => None,
+            })
+            .collect();
+        exact_runs.sort_unstable();
+        exact_runs.dedup();
+        let impossible = exact_runs.iter().any(|k| *k as usize > high.len());
+        goals.extend(exact_runs.into_iter().filter(|k| *k as usize <= high.len()).map(Rule::ExactRun));
+
+        DigitCounter {
+            rules,
+            goals,
+            impossible,
+            low,
+            high,
+            run_cap,
+            memo: HashMap::new(),
+        }
+    }
+
+    fn allowed(&self, last: Option<u8>, digit: u8) -> bool {
+        self.rules.rules.iter().all(|rule| match (rule, last) {
+            (Rule::NonDecreasing, Some(l)) => digit >= l,
+            (Rule::NonIncreasing, Some(l)) => digit <= l,
+            (Rule::AllowedDigits(mask), _) => mask & (1 << digit) != 0,
+            _ => true,
+        })
+    }
+
+    /// Finishes the current run and returns the updated set of met rules.
+    fn end_run(&self, run: u8, mut found: u32) -> u32 {
+        for (i, rule) in self.goals.iter().enumerate() {
+            if let Rule::ExactRun(k) = rule {
+                if run == *k {
+                    found |= 1 << i;
+                }
+            }
+        }
+        found
+    }
+
+    /// Applies the next digit, or returns `None` if that breaks a rule.
+    fn step(&self, last: Option<u8>, run: u8, found: u32, digit: u8) -> Option<(u8, u32)> {
+        if !self.allowed(last, digit) {
+            return None;
+        }
+        let (run, mut found) = if last == Some(digit) {
+            ((run + 1).min(self.run_cap), found)
+        } else if last.is_some() {
+            (1, self.end_run(run, found))
+        } else {
+            (1, found)
+        };
+        for rule in self.rules.rules.iter() {
+            if let Rule::MaxRun(k) = rule {
+                if run > *k {
+                    return None;
+                }
+            }
+        }
+        for (i, rule) in self.goals.iter().enumerate() {
+            if let Rule::MinRun(k) = rule {
+                if run >= *k {
+                    found |= 1 << i;
+                }
+            }
+        }
+        Some((run, found))
+    }
+
+    fn complete(&self, last: Option<u8>, run: u8, found: u32) -> bool {
+        if last.is_none() {
+            return self.rules.matches(0);
+        }
+        let found = self.end_run(run, found);
+        !self.impossible && found == (1 << self.goals.len()) - 1
+    }
+
+    fn digit_range(&self, pos: usize, tight_low: bool, tight_high: bool) -> std::ops::RangeInclusive<u8> {
+        let min = if tight_low { self.low[pos] } else { 0 };
+        let max = if tight_high { self.high[pos] } else { 9 };
+        min..=max
+    }
+
+    fn count(&mut self, pos: usize, tight_low: bool, tight_high: bool, last: Option<u8>, run: u8, found: u32) -> u64 {
+        if pos == self.high.len() {
+            return if self.complete(last, run, found) { 1 } else { 0 };
+        }
+
+        let key = (pos, tight_low, tight_high, last, run, found);
+        if let Some(count) = self.memo.get(&key) {
+            return *count;
+        }
+
+        let mut count = 0;
+        for digit in self.digit_range(pos, tight_low, tight_high) {
+            let tl = tight_low && digit == self.low[pos];
+            let th = tight_high && digit == self.high[pos];
+            let (l, r, fd) = if last.is_none() && digit == 0 {
+                (None, 0, found)
+            } else {
+                match self.step(last, run, found, digit) {
+                    Some((r, fd)) => (Some(digit), r, fd),
+                    None => continue,
+                }
+            };
+            count += self.count(pos + 1, tl, th, l, r, fd);
+        }
+
+        self.memo.insert(key, count);
+        count
+    }
+
+    /// Calls `f` for every password in the range, in increasing order. Any
+    /// branch the counter knows to be empty is skipped, so the time taken
+    /// depends on the number of passwords found rather than on the range.
+    #[allow(clippy::too_many_arguments)]
+    fn enumerate<F: FnMut(u64)>(
+        &mut self,
+        pos: usize,
+        tight_low: bool,
+        tight_high: bool,
+        last: Option<u8>,
+        run: u8,
+        found: u32,
+        prefix: u64,
+        f: &mut F,
+    ) {
+        if pos == self.high.len() {
+            if self.complete(last, run, found) {
+                f(prefix);
+            }
+            return;
+        }
+
+        for digit in self.digit_range(pos, tight_low, tight_high) {
+            let tl = tight_low && digit == self.low[pos];
+            let th = tight_high && digit == self.high[pos];
+            let (l, r, fd) = if last.is_none() && digit == 0 {
+                (None, 0, found)
+            } else {
+                match self.step(last, run, found, digit) {
+                    Some((r, fd)) => (Some(digit), r, fd),
+                    None => continue,
+                }
+            };
+            if self.count(pos + 1, tl, th, l, r, fd) > 0 {
+                self.enumerate(pos + 1, tl, th, l, r, fd, prefix * 10 + digit as u64, f);
+            }
+        }
+    }
+}
+
+#[test]
+fn test_count_passwords() {
+    let mut count_part1 = 0;
+    let mut count_part2 = 0;
+    for n in 0..=300_000 {
+        if valid_password(n) {
+            count_part1 += 1;
+            if password_contains_pair(n) {
+                count_part2 += 1;
+            }
+        }
+        if n % 997 == 0 || n % 100_000 == 99_999 {
+            assert_eq!(RuleSet::part1().count(0, n), count_part1, "up to {}", n);
+            assert_eq!(RuleSet::part2().count(0, n), count_part2, "up to {}", n);
+        }
+    }
+
+    for (a, b) in [(0, 0), (11, 11), (111, 123), (112233, 112233), (123444, 123444), (111122, 111122)] {
+        let part1 = (a..=b).filter(|i| valid_password(*i)).count() as u64;
+        let part2 = (a..=b).filter(|i| valid_password(*i)).filter(|i| password_contains_pair(*i)).count() as u64;
+        assert_eq!(RuleSet::part1().count(a, b), part1);
+        assert_eq!(RuleSet::part2().count(a, b), part2);
+    }
+
+    assert!(RuleSet::part2().count(0, u64::MAX) < RuleSet::part1().count(0, u64::MAX));
+}
+
+#[test]
+fn test_rule_sets() {
+    let rule_sets = [
+        RuleSet::new(vec![]),
+        RuleSet::new(vec![Rule::NonIncreasing, Rule::ExactRun(3)]),
+        RuleSet::new(vec![Rule::MinRun(2), Rule::MaxRun(3)]),
+        RuleSet::new(vec![Rule::ExactRun(1), Rule::ExactRun(2), Rule::Length(5)]),
+        RuleSet::new(vec![Rule::NonDecreasing, Rule::AllowedDigits(0b1010110101)]),
+        RuleSet::new(vec![Rule::AllowedDigits(0b10), Rule::Length(1)]),
+        RuleSet::new(vec![Rule::Length(1), Rule::MaxRun(0)]),
+        RuleSet::new(vec![Rule::ExactRun(2), Rule::ExactRun(30)]),
+        RuleSet::new((0..40).map(|k| Rule::MinRun(k % 3)).chain((0..40).map(|_| Rule::ExactRun(2))).collect()),
+    ];
+    for rules in rule_sets.iter() {
+        for (a, b) in [(0, 9), (0, 20_000), (4_321, 56_789), (99_000, 100_100)] {
+            let expected: Vec<u64> = (a..=b).filter(|n| rules.matches(*n)).collect();
+            let mut found = Vec::new();
+            rules.enumerate(a, b, |n| found.push(n));
+            assert_eq!(found, expected, "{:?} from {} to {}", rules, a, b);
+            assert_eq!(rules.count(a, b), expected.len() as u64);
+        }
+    }
+}
+
+fn parse_u64(input: &str) -> IResult<&str, u64> {
+    map_res(digit1, |s: &str| s.parse::<u64>())(input)
+}
+
+fn parse_range(input: &str) -> IResult<&str, (u64, u64)> {
+    separated_pair(parse_u64, tuple((space0, char('-'), space0)), parse_u64)(input)
+}
+
+fn parse_comment(input: &str) -> IResult<&str, &str> {
+    recognize(pair(char('#'), not_line_ending))(input)
+}
+
+fn parse_filler(input: &str) -> IResult<&str, ()> {
+    value((), many0(alt((multispace1, parse_comment))))(input)
+}
+
+/// Parses ranges separated by whitespace, where `#` starts a comment that
+/// runs until the end of the line.
+fn parse_ranges(input: &str) -> Result<Vec<(u64, u64)>, String> {
+    let line_number = |rest: &str| input[..input.len() - rest.len()].matches('\n').count() + 1;
+
+    let mut ranges = Vec::new();
+    let (mut rest, _) = parse_filler(input).map_err(|e| e.to_string())?;
+    while !rest.is_empty() {
+        match terminated(parse_range, parse_filler)(rest) {
+            Ok((r, (a, b))) => {
+                if a > b {
+                    return Err(format!("Line {}: range {}-{} starts after it ends", line_number(rest), a, b));
+                }
+                ranges.push((a, b));
+                rest = r;
+            }
+            Err(_) => return Err(format!("Line {}: expected a range such as 123456-654321", line_number(rest))),
+        }
+    }
+
+    if ranges.is_empty() {
+        return Err(String::from("No ranges found in the input"));
+    }
+    Ok(ranges)
+}
+
+#[test]
+fn test_parse_ranges() {
+    assert_eq!(parse_ranges("123-456\n"), Ok(vec![(123, 456)]));
+    assert_eq!(parse_ranges("123-456\r\n"), Ok(vec![(123, 456)]));
+    assert_eq!(parse_ranges("# puzzle input\n123 - 456 # mine\n"), Ok(vec![(123, 456)]));
+    assert_eq!(parse_ranges("1-2 3-4\n5-6\r\n\n7-8"), Ok(vec![(1, 2), (3, 4), (5, 6), (7, 8)]));
+
+    assert_eq!(parse_ranges("1-2\n# fine\n9-3\n"), Err(String::from("Line 3: range 9-3 starts after it ends")));
+    assert_eq!(parse_ranges("1-2\n3_4\n"), Err(String::from("Line 2: expected a range such as 123456-654321")));
+    assert_eq!(parse_ranges(""), Err(String::from("No ranges found in the input")));
+    assert_eq!(parse_ranges("# nothing here\n\n"), Err(String::from("No ranges found in the input")));
+}
+
+fn parse_rule(flag: &str, value: Option<&String>) -> Option<Rule> {
+    match flag {
+        "--non-decreasing" => Some(Rule::NonDecreasing),
+        "--non-increasing" => Some(Rule::NonIncreasing),
+        "--min-run" => Some(Rule::MinRun(value?.parse().ok()?)),
+        "--max-run" => Some(Rule::MaxRun(value?.parse().ok()?)),
+        "--exact-run" => Some(Rule::ExactRun(value?.parse().ok()?)),
+        "--length" => Some(Rule::Length(value?.parse().ok()?)),
+        "--digits" => {
+            let mut mask = 0;
+            for c in value?.chars() {
+                mask |= 1 << c.to_digit(10)?;
+            }
+            Some(Rule::AllowedDigits(mask))
+        }
+        _ => None,
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: day04 [--brute-force] [--list] [RULE...] < INPUT");
+    eprintln!("Rules: --non-decreasing, --non-increasing, --min-run <N>, --max-run <N>,");
+    eprintln!("       --exact-run <N>, --length <N>, --digits <DIGITS>");
+    std::process::exit(1);
+}
+
 fn main() -> io::Result<()> {
+    let mut brute_force = false;
+    let mut list = false;
+    let mut rules = Vec::new();
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut i = 0;
+    while i < args.len() {
+        match &args[i][..] {
+            "--brute-force" => brute_force = true,
+            "--list" => list = true,
+            flag => {
+                let takes_value = !matches!(flag, "--non-decreasing" | "--non-increasing");
+                rules.push(parse_rule(flag, args.get(i + 1)).unwrap_or_else(|| usage()));
+                if takes_value {
+                    i += 1;
+                }
+            }
+        }
+        i += 1;
+    }
+
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
-    let input: Vec<&str> = input.split('-').collect();
+    let ranges = match parse_ranges(&input) {
+        Ok(ranges) => ranges,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let rules = if rules.is_empty() { None } else { Some(RuleSet::new(rules)) };
 
-    let a: u32 = input[0].parse().unwrap();
-    let b: u32 = input[1].parse().unwrap();
+    for &(a, b) in ranges.iter() {
+        if ranges.len() > 1 && !list {
+            println!("Range {}-{}:", a, b);
+        }
+
+        if let Some(rules) = rules.as_ref() {
+            if list {
+                let stdout = io::stdout();
+                let mut out = io::BufWriter::new(stdout.lock());
+                if brute_force {
+                    for n in (a..=b).filter(|n| rules.matches(*n)) {
+                        writeln!(out, "{}", n)?;
+                    }
+                } else {
+                    let mut result = Ok(());
+                    rules.enumerate(a, b, |n| {
+                        if result.is_ok() {
+                            result = writeln!(out, "{}", n);
+                        }
+                    });
+                    result?;
+                }
+            } else {
+                let count = if brute_force {
+                    (a..=b).filter(|n| rules.matches(*n)).count() as u64
+                } else {
+                    rules.count(a, b)
+                };
+                println!("Number of passwords that meet the given rules: {}", count);
+            }
+            continue;
+        }
 
-    let count_part1 = (a..=b).filter(|i| valid_password(*i)).count();
-    let count_part2 = (a..=b).filter(|i| valid_password(*i)).filter(|i| password_contains_pair(*i)).count();
+        let count_part1;
+        let count_part2;
+        if brute_force {
+            count_part1 = (a..=b).filter(|i| valid_password(*i)).count() as u64;
+            count_part2 = (a..=b).filter(|i| valid_password(*i)).filter(|i| password_contains_pair(*i)).count() as u64;
+        } else {
+            count_part1 = RuleSet::part1().count(a, b);
+            count_part2 = RuleSet::part2().count(a, b);
+        }
 
-    println!("Number of passwords that meet the criteria: {}", count_part1);
-    println!("Number of passwords that meet the stricter criteria: {}", count_part2);
+        println!("Number of passwords that meet the criteria: {}", count_part1);
+        println!("Number of passwords that meet the stricter criteria: {}", count_part2);
+    }
 
     Ok(())
 }
@@ -1,20 +1,335 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fmt::Write as _;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::time::Instant;
 
 fn orbit_str_to_tuple(s: &str) -> (&str, &str) {
     let mut split = s.split(')');
     (split.next().unwrap(), split.next().unwrap())
 }
 
-fn get_orbits_by_orbiter<'a>(orbiter: &'a str, orbits: &HashMap<&'a str, &'a str>) -> Vec<&'a str> {
-    let mut s = orbiter;
-    let mut v = Vec::new();
-    while orbits.contains_key(&s) {
-        v.push(*orbits.get(&s).unwrap());
-        s = orbits.get(&s).unwrap();
+enum MapError<'a> {
+    Malformed { line: usize },
+    DuplicateOrbiter { orbiter: &'a str, lines: Vec<usize> },
+    Cycle { bodies: Vec<&'a str>, lines: Vec<usize> },
+    MultipleRoots { roots: Vec<(&'a str, usize)> },
+}
+
+fn join_lines(lines: &[usize]) -> String {
+    lines.iter().map(|l| l.to_string()).collect::<Vec<String>>().join(", ")
+}
+
+impl fmt::Display for MapError<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Malformed { line } => write!(f, "Line {}: expected an orbit such as COM)B", line),
+            MapError::DuplicateOrbiter { orbiter, lines } => {
+                write!(f, "Lines {}: {} orbits more than one body", join_lines(lines), orbiter)
+            }
+            MapError::Cycle { bodies, lines } => {
+                write!(f, "Lines {}: orbits form a cycle through {}", join_lines(lines), bodies.join(", "))
+            }
+            MapError::MultipleRoots { roots } => {
+                let roots: Vec<String> = roots.iter().map(|(r, l)| format!("{} (line {})", r, l)).collect();
+                write!(f, "There is more than one body at the center: {}", roots.join(", "))
+            }
+        }
+    }
+}
+
+/// Checks that the orbits form a single tree. Takes the non-empty input lines
+/// along with their line numbers.
+fn validate<'a>(lines: &[(usize, &'a str)]) -> Vec<MapError<'a>> {
+    let mut errors = Vec::new();
+
+    let mut orbits: HashMap<&str, (&str, usize)> = HashMap::new();
+    let mut orbiter_lines: BTreeMap<&str, Vec<usize>> = BTreeMap::new();
+    let mut first_seen: HashMap<&str, usize> = HashMap::new();
+    for &(line, s) in lines.iter() {
+        let split: Vec<&str> = s.split(')').collect();
+        if split.len() != 2 || split.iter().any(|name| name.is_empty()) {
+            errors.push(MapError::Malformed { line });
+            continue;
+        }
+        let (orbitee, orbiter) = (split[0], split[1]);
+        orbits.entry(orbiter).or_insert((orbitee, line));
+        orbiter_lines.entry(orbiter).or_default().push(line);
+        first_seen.entry(orbitee).or_insert(line);
+        first_seen.entry(orbiter).or_insert(line);
+    }
+
+    for (orbiter, lines) in orbiter_lines {
+        if lines.len() > 1 {
+            errors.push(MapError::DuplicateOrbiter { orbiter, lines });
+        }
+    }
+
+    // Follow every body towards the center. Running into a body that was
+    // already seen during the same walk means there is a cycle.
+    let mut walked: HashMap<&str, usize> = HashMap::new();
+    let mut orbiters: Vec<&str> = orbits.keys().cloned().collect();
+    orbiters.sort_unstable_by_key(|o| orbits[o].1);
+    for (walk, start) in orbiters.into_iter().enumerate() {
+        let mut path = Vec::new();
+        let mut current = start;
+        while let Some(&(orbitee, line)) = orbits.get(current) {
+            if let Some(&w) = walked.get(current) {
+                if w == walk {
+                    let from = path.iter().position(|(body, _)| *body == current).unwrap();
+                    let (bodies, mut lines): (Vec<&str>, Vec<usize>) = path[from..].iter().cloned().unzip();
+                    lines.sort_unstable();
+                    errors.push(MapError::Cycle { bodies, lines });
+                }
+                break;
+            }
+            walked.insert(current, walk);
+            path.push((current, line));
+            current = orbitee;
+        }
+    }
+
+    let mut roots: Vec<(&str, usize)> = first_seen
+        .iter()
+        .filter(|(name, _)| !orbits.contains_key(*name))
+        .map(|(name, line)| (*name, *line))
+        .collect();
+    if roots.len() > 1 {
+        roots.sort_unstable_by_key(|(_, line)| *line);
+        errors.push(MapError::MultipleRoots { roots });
+    }
+
+    errors
+}
+
+#[test]
+fn test_validate() {
+    let errors = |input: &'static str| -> Vec<String> {
+        let lines: Vec<(usize, &str)> = input.split('\n').enumerate().map(|(i, s)| (i + 1, s)).filter(|(_, s)| !s.is_empty()).collect();
+        validate(&lines).iter().map(|e| e.to_string()).collect()
+    };
+
+    assert!(errors("COM)B\nB)C\nB)D\n").is_empty());
+    assert_eq!(errors("COM)A\nCOM)B\nB)A"), vec!["Lines 1, 3: A orbits more than one body"]);
+    assert_eq!(errors("COM)B\nA)A"), vec!["Lines 2: orbits form a cycle through A"]);
+    assert_eq!(
+        errors("COM)B\nC)D\nD)E\nE)C\nD)F"),
+        vec!["Lines 2, 3, 4: orbits form a cycle through D, C, E"]
+    );
+    assert_eq!(
+        errors("COM)B\nB)C\n\nX)Y"),
+        vec!["There is more than one body at the center: COM (line 1), X (line 4)"]
+    );
+    assert_eq!(
+        errors("COM)B\nBC\n\nB)"),
+        vec!["Line 2: expected an orbit such as COM)B", "Line 4: expected an orbit such as COM)B"]
+    );
+}
+
+struct OrbitTree<'a> {
+    names: Vec<&'a str>,
+    index: HashMap<&'a str, usize>,
+    parents: Vec<Option<usize>>,
+    depths: Vec<usize>,
+    subtree_sizes: Vec<usize>,
+}
+
+impl<'a> OrbitTree<'a> {
+    fn new(orbits: &HashMap<&'a str, &'a str>) -> Self {
+        let mut sorted: Vec<(&&str, &&str)> = orbits.iter().collect();
+        sorted.sort_unstable();
+
+        let mut names: Vec<&str> = Vec::new();
+        let mut index: HashMap<&str, usize> = HashMap::new();
+        for (orbiter, orbitee) in sorted {
+            for name in [orbiter, orbitee] {
+                index.entry(name).or_insert_with(|| {
+                    names.push(name);
+                    names.len() - 1
+                });
+            }
+        }
+
+        let mut parents = vec![None; names.len()];
+        for (orbiter, orbitee) in orbits.iter() {
+            parents[index[orbiter]] = Some(index[orbitee]);
+        }
+
+        // Walk up from every body until reaching one whose depth is known,
+        // then fill in the depths on the way back down. This visits every
+        // body only once in total.
+        let mut depths: Vec<Option<usize>> = vec![None; names.len()];
+        for i in 0..names.len() {
+            let mut chain = Vec::new();
+            let mut current = Some(i);
+            let mut depth = 0;
+            while let Some(c) = current {
+                if let Some(d) = depths[c] {
+                    depth = d + 1;
+                    break;
+                }
+                chain.push(c);
+                current = parents[c];
+            }
+            for c in chain.into_iter().rev() {
+                depths[c] = Some(depth);
+                depth += 1;
+            }
+        }
+        let depths: Vec<usize> = depths.into_iter().map(|d| d.unwrap()).collect();
+
+        let mut by_depth: Vec<usize> = (0..names.len()).collect();
+        by_depth.sort_unstable_by_key(|i| std::cmp::Reverse(depths[*i]));
+        let mut subtree_sizes = vec![1; names.len()];
+        for i in by_depth {
+            if let Some(p) = parents[i] {
+                subtree_sizes[p] += subtree_sizes[i];
+            }
+        }
+
+        OrbitTree {
+            names,
+            index,
+            parents,
+            depths,
+            subtree_sizes,
+        }
+    }
+
+    fn total_orbits(&self) -> usize {
+        self.depths.iter().sum()
+    }
+
+    fn depth(&self, name: &str) -> Option<usize> {
+        self.index.get(name).map(|i| self.depths[*i])
+    }
+
+    /// The number of bodies orbiting this one, directly or indirectly, plus
+    /// the body itself.
+    fn subtree_size(&self, name: &str) -> Option<usize> {
+        self.index.get(name).map(|i| self.subtree_sizes[*i])
+    }
+
+    fn common_ancestor(&self, a: usize, b: usize) -> Option<usize> {
+        let (mut a, mut b) = (Some(a), Some(b));
+        while let (Some(i), Some(j)) = (a, b) {
+            if i == j {
+                return a;
+            }
+            if self.depths[i] >= self.depths[j] {
+                a = self.parents[i];
+            } else {
+                b = self.parents[j];
+            }
+        }
+        None
+    }
+
+    fn lowest_common_ancestor(&self, a: &str, b: &str) -> Option<&'a str> {
+        let (a, b) = (*self.index.get(a)?, *self.index.get(b)?);
+        self.common_ancestor(a, b).map(|i| self.names[i])
+    }
+
+    /// All bodies from `a` up to the common ancestor and back down to `b`.
+    fn path(&self, a: &str, b: &str) -> Option<Vec<&'a str>> {
+        let (mut a, mut b) = (*self.index.get(a)?, *self.index.get(b)?);
+        let ancestor = self.common_ancestor(a, b)?;
+
+        let mut up = vec![self.names[a]];
+        while a != ancestor {
+            a = self.parents[a].unwrap();
+            up.push(self.names[a]);
+        }
+        let mut down = Vec::new();
+        while b != ancestor {
+            down.push(self.names[b]);
+            b = self.parents[b].unwrap();
+        }
+        up.extend(down.into_iter().rev());
+        Some(up)
+    }
+
+    /// The number of orbital transfers needed to go from the body `a` orbits
+    /// to the body `b` orbits.
+    fn transfers(&self, a: &str, b: &str) -> Option<usize> {
+        let a = self.parents[*self.index.get(a)?]?;
+        let b = self.parents[*self.index.get(b)?]?;
+        let ancestor = self.common_ancestor(a, b)?;
+        Some(self.depths[a] + self.depths[b] - 2 * self.depths[ancestor])
     }
-    v
+
+    /// Renders the tree for graphviz, with every body on the row matching its
+    /// depth. Optionally marks the path between two bodies and their common
+    /// ancestor.
+    fn to_dot(&self, highlight: Option<(&str, &str)>) -> String {
+        let mut path: HashSet<(&str, &str)> = HashSet::new();
+        let mut on_path: HashSet<&str> = HashSet::new();
+        let mut ancestor = None;
+        if let Some((a, b)) = highlight {
+            if let Some(p) = self.path(a, b) {
+                for w in p.windows(2) {
+                    path.insert((w[0], w[1]));
+                    path.insert((w[1], w[0]));
+                }
+                on_path.extend(p);
+                ancestor = self.lowest_common_ancestor(a, b);
+            }
+        }
+
+        let mut dot = String::from("digraph orbits {\n    node [shape=circle, fontsize=10];\n");
+
+        let mut ranks: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+        for (i, name) in self.names.iter().enumerate() {
+            ranks.entry(self.depths[i]).or_default().push(name);
+        }
+        for names in ranks.values() {
+            let names: Vec<String> = names.iter().map(|n| format!("\"{}\"", n)).collect();
+            writeln!(dot, "    {{ rank=same; {}; }}", names.join("; ")).unwrap();
+        }
+
+        for name in self.names.iter().filter(|n| on_path.contains(*n)) {
+            let color = if Some(*name) == ancestor { "gold" } else { "lightpink" };
+            writeln!(dot, "    \"{}\" [style=filled, fillcolor={}];", name, color).unwrap();
+        }
+
+        for (i, parent) in self.parents.iter().enumerate() {
+            if let Some(p) = parent {
+                let (orbitee, orbiter) = (self.names[*p], self.names[i]);
+                let style = if path.contains(&(orbitee, orbiter)) { " [color=red, penwidth=2]" } else { "" };
+                writeln!(dot, "    \"{}\" -> \"{}\"{};", orbitee, orbiter, style).unwrap();
+            }
+        }
+
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+#[test]
+fn test_orbit_tree() {
+    let example = "COM)B\nB)C\nC)D\nD)E\nE)F\nB)G\nG)H\nD)I\nE)J\nJ)K\nK)L";
+    let orbits: HashMap<&str, &str> = example.split('\n').map(orbit_str_to_tuple).map(|(a, b)| (b, a)).collect();
+    let tree = OrbitTree::new(&orbits);
+    assert_eq!(tree.total_orbits(), 42);
+    assert_eq!(tree.depth("L"), Some(7));
+    assert_eq!(tree.subtree_size("E"), Some(5));
+    assert_eq!(tree.subtree_size("COM"), Some(12));
+    assert_eq!(tree.lowest_common_ancestor("K", "I"), Some("D"));
+    assert_eq!(tree.path("K", "I"), Some(vec!["K", "J", "E", "D", "I"]));
+
+    assert_eq!(tree.lowest_common_ancestor("D", "L"), Some("D"));
+    assert_eq!(tree.path("D", "L"), Some(vec!["D", "E", "J", "K", "L"]));
+    assert_eq!(tree.path("L", "D"), Some(vec!["L", "K", "J", "E", "D"]));
+    assert_eq!(tree.transfers("D", "L"), Some(4));
+    assert_eq!(tree.path("L", "X"), None);
+
+    let example = format!("{}\nK)YOU\nI)SAN", example);
+    let orbits: HashMap<&str, &str> = example.split('\n').map(orbit_str_to_tuple).map(|(a, b)| (b, a)).collect();
+    let tree = OrbitTree::new(&orbits);
+    assert_eq!(tree.transfers("YOU", "SAN"), Some(4));
 }
 
 fn get_orbit_count_by_orbiter(orbiter: &str, orbits: &HashMap<&str, &str>) -> usize {
@@ -27,35 +342,110 @@ fn get_orbit_count_by_orbiter(orbiter: &str, orbits: &HashMap<&str, &str>) -> us
     count
 }
 
+/// Times the orbit count on a single long chain of bodies, where walking to
+/// the center from every body separately takes quadratic time.
+fn benchmark() {
+    for n in [1_000, 10_000, 100_000, 1_000_000] {
+        let names: Vec<String> = (0..=n).map(|i| format!("B{}", i)).collect();
+        let orbits: HashMap<&str, &str> = (1..=n).map(|i| (&names[i][..], &names[i - 1][..])).collect();
+
+        let start = Instant::now();
+        let count = OrbitTree::new(&orbits).total_orbits();
+        println!("Chain of {} orbits, memoized depths: {} orbits in {:?}", n, count, start.elapsed());
+
+        if n <= 10_000 {
+            let start = Instant::now();
+            let count: usize = orbits.keys().map(|s| get_orbit_count_by_orbiter(s, &orbits)).sum();
+            println!("Chain of {} orbits, walking to the center: {} orbits in {:?}", n, count, start.elapsed());
+        }
+    }
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: day06 [--path <BODY> <BODY>]... [--body <BODY>]... [--dot <FILE> [--highlight]] < INPUT");
+    eprintln!("       day06 --bench");
+    std::process::exit(1);
+}
+
 fn main() -> io::Result<()> {
+    if env::args().nth(1).as_deref() == Some("--bench") {
+        benchmark();
+        return Ok(());
+    }
+
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
-    let input: Vec<&str> = input.split('\n').filter(|s| s.len() > 0).collect();
+    let input: Vec<(usize, &str)> = input
+        .split('\n')
+        .enumerate()
+        .map(|(i, s)| (i + 1, s.trim_end_matches('\r')))
+        .filter(|(_, s)| s.len() > 0)
+        .collect();
+
+    let errors = validate(&input);
+    if !errors.is_empty() {
+        for e in errors.iter() {
+            eprintln!("{}", e);
+        }
+        std::process::exit(1);
+    }
 
     let mut orbits: HashMap<&str, &str> = HashMap::new();
 
-    for (orbitee, orbiter) in input.iter().map(|s| orbit_str_to_tuple(s)) {
+    for (orbitee, orbiter) in input.iter().map(|(_, s)| orbit_str_to_tuple(s)) {
         orbits.insert(orbiter, orbitee);
     }
 
-    let orbit_count: usize = orbits.keys().map(|s| get_orbit_count_by_orbiter(s, &orbits)).sum();
+    let tree = OrbitTree::new(&orbits);
+    println!("The total number of orbits in the solar system: {}", tree.total_orbits());
 
-    println!("The total number of orbits in the solar system: {}", orbit_count);
-
-    let orbits_you = get_orbits_by_orbiter("YOU", &orbits);
-    let orbits_santa = get_orbits_by_orbiter("SAN", &orbits);
-
-    let mut num_steps = orbits_you.len() + orbits_santa.len();
-    let mut py = orbits_you.len() - 1;
-    let mut ps = orbits_santa.len() - 1;
+    match tree.transfers("YOU", "SAN") {
+        Some(num_steps) => println!("The number of steps that need to be taken is: {}", num_steps),
+        None => println!("There is no way to get from YOU to SAN"),
+    }
 
-    while orbits_you[py] == orbits_santa[ps] {
-        num_steps -= 2;
-        py -= 1;
-        ps -= 1;
+    let mut dot = None;
+    let mut highlight = false;
+    let args: Vec<String> = env::args().skip(1).collect();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--path" => {
+                let (a, b) = match (args.next(), args.next()) {
+                    (Some(a), Some(b)) => (a, b),
+                    _ => usage(),
+                };
+                match tree.path(a, b) {
+                    Some(path) => {
+                        println!("Path from {} to {}: {}", a, b, path.join(" -> "));
+                        println!("Common ancestor: {}", tree.lowest_common_ancestor(a, b).unwrap());
+                        match tree.transfers(a, b) {
+                            Some(t) => println!("Orbital transfers needed: {}", t),
+                            None => println!("Orbital transfers needed: none possible"),
+                        }
+                    }
+                    None => println!("There is no path from {} to {}", a, b),
+                }
+            }
+            "--body" => {
+                let name = args.next().unwrap_or_else(|| usage());
+                match (tree.depth(name), tree.subtree_size(name)) {
+                    (Some(depth), Some(size)) => {
+                        println!("{} orbits {} bodies and is orbited by {} bodies", name, depth, size - 1)
+                    }
+                    _ => println!("There is no body named {}", name),
+                }
+            }
+            "--dot" => dot = Some(args.next().unwrap_or_else(|| usage())),
+            "--highlight" => highlight = true,
+            _ => usage(),
+        }
     }
 
-    println!("The number of steps that need to be taken is: {}", num_steps);
+    if let Some(filename) = dot {
+        let highlight = if highlight { Some(("YOU", "SAN")) } else { None };
+        fs::write(filename, tree.to_dot(highlight))?;
+    }
 
     Ok(())
 }
@@ -2,10 +2,17 @@ use std::io;
 use std::io::prelude::*;
 
 use std::collections::HashSet;
+#[cfg(feature = "async")]
+use std::cell::Cell;
+#[cfg(feature = "async")]
+use std::rc::Rc;
 
 use intcode::Program;
 use intcode::Number;
 
+#[cfg(feature = "async")]
+use adventofcode_rust_2019::driver::{channel, run_machine, Executor, Receiver, Sender};
+
 fn get_result_part1(settings: Vec<Number>, program: Vec<Number>) -> Number {
     let mut output = 0;
 
@@ -20,6 +27,7 @@ fn get_result_part1(settings: Vec<Number>, program: Vec<Number>) -> Number {
     output
 }
 
+#[cfg(not(feature = "async"))]
 fn get_result_part2(settings: Vec<Number>, program: Vec<Number>) -> Number {
     let mut programs: Vec<Program> = Vec::new();
     let num_programs = settings.len();
@@ -45,6 +53,34 @@ fn get_result_part2(settings: Vec<Number>, program: Vec<Number>) -> Number {
     programs[num_programs - 1].last_output().unwrap()
 }
 
+/// Connects the amplifiers in a loop of channels and lets the executor run
+/// whichever amplifier has input waiting.
+#[cfg(feature = "async")]
+fn get_result_part2(settings: Vec<Number>, program: Vec<Number>) -> Number {
+    let (mut senders, receivers): (Vec<Sender>, Vec<Receiver>) = settings.iter().map(|_| channel()).unzip();
+    for (sender, setting) in senders.iter().zip(settings.iter()) {
+        sender.send(*setting);
+    }
+    senders[0].send(0);
+    senders.rotate_left(1);
+
+    let mut executor = Executor::new();
+    let result = Rc::new(Cell::new(None));
+    let num_programs = settings.len();
+    for (i, (receiver, sender)) in receivers.into_iter().zip(senders).enumerate() {
+        let machine = run_machine(Program::new(program.clone()), receiver, sender);
+        let result = result.clone();
+        executor.spawn(async move {
+            let program = machine.await;
+            if i == num_programs - 1 {
+                result.set(program.last_output());
+            }
+        });
+    }
+    executor.run();
+    result.get().unwrap()
+}
+
 #[test]
 fn test_part1() {
     let v = vec![3,15,3,16,1002,16,10,16,1,16,15,15,4,15,99,0,0];
@@ -1,6 +1,14 @@
-use std::collections::HashSet;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
 use std::io;
 use std::io::prelude::*;
+use std::path::Path;
+use std::time::Instant;
+
+use adventofcode_rust_2019::rng::Rng;
 
 fn gcd(a: i32, b: i32) -> i32 {
     if b == 0 {
@@ -57,18 +65,6 @@ fn get_num_in_sight(input: &Vec<Vec<u8>>, xc: i32, yc: i32) -> u32 {
     count
 }
 
-fn get_unique_fractions(num: i32) -> HashSet<(i32, i32)> {
-    let mut s = HashSet::new();
-    for i in -num..=num {
-        for j in -num..=num {
-            if i != 0 || j != 0 {
-                s.insert(simplify_fraction(i, j));
-            }
-        }
-    }
-    s
-}
-
 fn simplify_fraction(mut a: i32, mut b: i32) -> (i32, i32) {
     let g = gcd(a.abs(), b.abs());
     if g > 0 {
@@ -78,71 +74,355 @@ fn simplify_fraction(mut a: i32, mut b: i32) -> (i32, i32) {
     (a, b)
 }
 
-/// This works. I do not know how.
-fn vector_to_angle(v: (i32, i32)) -> f64 {
-    let (a, b) = v;
+fn get_asteroids(input: &[Vec<u8>]) -> Vec<(i32, i32)> {
+    let mut asteroids = Vec::new();
+    for (q, row) in input.iter().enumerate() {
+        for (p, cell) in row.iter().enumerate() {
+            if *cell == 1 {
+                asteroids.push((p as i32, q as i32));
+            }
+        }
+    }
+    asteroids
+}
+
+/// For every asteroid, the number of other asteroids it can see. Asteroids
+/// that lie in the same direction block each other, so this is the number of
+/// distinct directions to the other asteroids.
+fn get_visibility_counts(asteroids: &[(i32, i32)]) -> Vec<usize> {
+    let mut directions: HashSet<(i32, i32)> = HashSet::new();
+    asteroids
+        .iter()
+        .map(|(xc, yc)| {
+            directions.clear();
+            for (p, q) in asteroids.iter() {
+                if (p, q) != (xc, yc) {
+                    directions.insert(simplify_fraction(p - xc, q - yc));
+                }
+            }
+            directions.len()
+        })
+        .collect()
+}
+
+/// Generates a square map where roughly three in ten cells hold an asteroid.
+fn generate_map(size: usize, seed: u64) -> Vec<Vec<u8>> {
+    let mut rng = Rng::new(seed);
+    let mut input = vec![vec![0; size]; size];
+    for row in input.iter_mut() {
+        for cell in row.iter_mut() {
+            *cell = if rng.below(10) < 3 { 1 } else { 0 };
+        }
+    }
+    input
+}
+
+#[test]
+fn test_visibility_counts() {
+    let examples = [
+        (".#..#\n.....\n#####\n....#\n...##", (3, 4), 8),
+        (
+            "......#.#.\n#..#.#....\n..#######.\n.#.#.###..\n.#..#.....\n\
+             ..#....#.#\n#..#....#.\n.##.#..###\n##...#..#.\n.#....####",
+            (5, 8),
+            33,
+        ),
+        (LARGE_EXAMPLE, (11, 13), 210),
+    ];
+    for (map, best, count) in examples {
+        let asteroids = get_asteroids(&parse_map(map));
+        let counts = get_visibility_counts(&asteroids);
+        assert_eq!(counts.iter().max(), Some(&count));
+        assert_eq!(counts[asteroids.iter().position(|a| *a == best).unwrap()], count);
+    }
+
+    for (size, seed) in [(1, 1), (7, 2), (15, 3), (24, 4)] {
+        let input = generate_map(size, seed);
+        let asteroids = get_asteroids(&input);
+        for (&(p, q), count) in asteroids.iter().zip(get_visibility_counts(&asteroids)) {
+            assert_eq!(count, get_num_in_sight(&input, p, q) as usize, "({}, {}) on map {}", p, q, seed);
+        }
+    }
+}
+
+fn benchmark(size: usize) {
+    let input = generate_map(size, 0x2545f4914f6cdd1d);
+    let asteroids = get_asteroids(&input);
+    println!("Map of {}x{} with {} asteroids", size, size, asteroids.len());
 
-    let x = a as f64;
-    let y = b as f64;
+    let start = Instant::now();
+    let best = get_visibility_counts(&asteroids).into_iter().max().unwrap_or(0);
+    println!("Grouping by direction: best sees {} in {:?}", best, start.elapsed());
 
-    let mut angle = y.atan2(x);
-    angle += std::f64::consts::FRAC_PI_2;
-    if angle < 0.0 {
-        angle += 2f64 * std::f64::consts::PI;
+    if size <= 60 {
+        let start = Instant::now();
+        let best = asteroids.iter().map(|(p, q)| get_num_in_sight(&input, *p, *q)).max().unwrap_or(0);
+        println!("Scanning the grid: best sees {} in {:?}", best, start.elapsed());
     }
-    angle
+}
+
+/// Orders directions clockwise, starting from straight up. Note that the y
+/// axis points down. The directions are first split into the right half
+/// (including up) and the left half (including down), and within a half the
+/// sign of the cross product tells which direction comes first. Everything is
+/// done in integers, so no two distinct directions ever compare as equal.
+fn compare_directions(v: &(i32, i32), w: &(i32, i32)) -> Ordering {
+    let half = |(a, b): (i32, i32)| if a > 0 || (a == 0 && b < 0) { 0 } else { 1 };
+    let (a1, b1) = (v.0 as i64, v.1 as i64);
+    let (a2, b2) = (w.0 as i64, w.1 as i64);
+
+    half(*v).cmp(&half(*w)).then_with(|| 0.cmp(&(a1 * b2 - b1 * a2)))
+}
+
+#[test]
+fn test_compare_directions() {
+    let mut directions: HashSet<(i32, i32)> = HashSet::new();
+    for i in -6..=6 {
+        for j in -6..=6 {
+            if i != 0 || j != 0 {
+                directions.insert(simplify_fraction(i, j));
+            }
+        }
+    }
+    let mut directions: Vec<(i32, i32)> = directions.into_iter().collect();
+    directions.sort_by(compare_directions);
+    assert_eq!(directions[0], (0, -1));
+    assert_eq!(directions[directions.len() / 4], (1, 0));
+    assert_eq!(directions[directions.len() / 2], (0, 1));
+    assert_eq!(directions[3 * directions.len() / 4], (-1, 0));
+
+    let angle = |(a, b): (i32, i32)| {
+        let angle = (b as f64).atan2(a as f64) + std::f64::consts::FRAC_PI_2;
+        if angle < 0.0 { angle + 2.0 * std::f64::consts::PI } else { angle }
+    };
+    for w in directions.windows(2) {
+        assert!(angle(w[0]) < angle(w[1]), "{:?} should come before {:?}", w[0], w[1]);
+    }
+
+    assert_eq!(compare_directions(&(99_999, -100_000), &(100_000, -100_001)), Ordering::Less);
+    assert_eq!(compare_directions(&(-99_999, 100_000), &(-100_000, 100_001)), Ordering::Less);
+}
+
+/// The order in which the laser at the station vaporizes every other
+/// asteroid. Asteroids are grouped by direction, nearest first, and every
+/// rotation of the laser takes the first remaining asteroid of each group.
+fn get_vaporization_order(input: &[Vec<u8>], station: (i32, i32)) -> Vec<(i32, i32)> {
+    let (xc, yc) = station;
+    let mut groups: HashMap<(i32, i32), Vec<(i32, i32)>> = HashMap::new();
+    for (q, row) in input.iter().enumerate() {
+        for (p, cell) in row.iter().enumerate() {
+            let (p, q) = (p as i32, q as i32);
+            if *cell == 1 && (p, q) != station {
+                groups.entry(simplify_fraction(p - xc, q - yc)).or_default().push((p, q));
+            }
+        }
+    }
+
+    let mut groups: Vec<_> = groups.into_iter().collect();
+    groups.sort_by(|(v, _), (w, _)| compare_directions(v, w));
+    for (_, asteroids) in groups.iter_mut() {
+        asteroids.sort_by_key(|(p, q)| (p - xc).abs() + (q - yc).abs());
+    }
+
+    let total: usize = groups.iter().map(|(_, a)| a.len()).sum();
+    let mut order = Vec::with_capacity(total);
+    let mut rotation = 0;
+    while order.len() < total {
+        for (_, asteroids) in groups.iter() {
+            if let Some(a) = asteroids.get(rotation) {
+                order.push(*a);
+            }
+        }
+        rotation += 1;
+    }
+    order
+}
+
+#[cfg(test)]
+const LARGE_EXAMPLE: &str = "\
+.#..##.###...#######
+##.############..##.
+.#.######.########.#
+.###.#######.####.#.
+#####.##.#.##.###.##
+..#####..#.#########
+####################
+#.####....###.#.#.##
+##.#################
+#####.##.###..####..
+..######..##.#######
+####.##.####...##..#
+.#####..#.######.###
+##...#.##########...
+#.##########.#######
+.####.#.###.###.#.##
+....##.##.###..#####
+.#.#.###########.###
+#.#.#.#####.####.###
+###.##.####.##.#..##";
+
+#[test]
+fn test_vaporization_order() {
+    let order = get_vaporization_order(&parse_map(LARGE_EXAMPLE), (11, 13));
+    assert_eq!(order.len(), 299);
+    assert_eq!(order[0], (11, 12));
+    assert_eq!(order[1], (12, 1));
+    assert_eq!(order[199], (8, 2));
+    assert_eq!(order[298], (11, 1));
+    assert_eq!(order.get(299), None);
+
+    let order = get_vaporization_order(&parse_map(".#.\n.#.\n#.."), (1, 1));
+    assert_eq!(order, vec![(1, 0), (0, 2)]);
+    assert_eq!(order.get(2), None);
+}
+
+/// Blue for the fewest visible asteroids through to red for the most.
+fn heat_color(count: usize, min: usize, max: usize) -> String {
+    let t = if max > min { (count - min) as f64 / (max - min) as f64 } else { 1.0 };
+    format!("rgb({},{},{})", (255.0 * t) as u8, (64.0 * (1.0 - t)) as u8, (255.0 * (1.0 - t)) as u8)
+}
+
+/// Draws the map with every asteroid colored by how many others it sees and
+/// the station circled. Vaporized asteroids are drawn as gray outlines, and
+/// if a target is given the laser is drawn from the station to it.
+fn render_svg(
+    input: &[Vec<u8>],
+    asteroids: &[(i32, i32)],
+    counts: &[usize],
+    station: (i32, i32),
+    vaporized: &HashSet<(i32, i32)>,
+    target: Option<(i32, i32)>,
+) -> String {
+    let width = input.first().map_or(0, |row| row.len());
+    let height = input.len();
+    let min = counts.iter().copied().min().unwrap_or(0);
+    let max = counts.iter().copied().max().unwrap_or(0);
+
+    let mut svg = String::new();
+    writeln!(svg, r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="-1 -1 {} {}">"#, width + 1, height + 1).unwrap();
+    writeln!(svg, r#"<rect x="-1" y="-1" width="100%" height="100%" fill="black"/>"#).unwrap();
+
+    for (&(x, y), &count) in asteroids.iter().zip(counts.iter()) {
+        if vaporized.contains(&(x, y)) {
+            writeln!(svg, r#"<circle cx="{}" cy="{}" r="0.3" fill="none" stroke="gray" stroke-width="0.05"/>"#, x, y).unwrap();
+        } else if (x, y) != station {
+            writeln!(svg, r#"<circle cx="{}" cy="{}" r="0.4" fill="{}"><title>{}</title></circle>"#, x, y, heat_color(count, min, max), count)
+                .unwrap();
+        }
+    }
+
+    if let Some((x, y)) = target {
+        writeln!(
+            svg,
+            r#"<line x1="{}" y1="{}" x2="{}" y2="{}" stroke="lime" stroke-width="0.1"/>"#,
+            station.0, station.1, x, y
+        )
+        .unwrap();
+    }
+
+    let (x, y) = station;
+    let count = asteroids.iter().position(|a| *a == station).map_or(0, |i| counts[i]);
+    writeln!(svg, r#"<circle cx="{}" cy="{}" r="0.4" fill="{}"><title>{}</title></circle>"#, x, y, heat_color(count, min, max), count).unwrap();
+    writeln!(svg, r#"<circle cx="{}" cy="{}" r="0.6" fill="none" stroke="white" stroke-width="0.1"/>"#, x, y).unwrap();
+
+    svg.push_str("</svg>\n");
+    svg
+}
+
+/// Writes one frame per vaporized asteroid, each showing the laser on its
+/// way to that asteroid and everything vaporized before it.
+fn write_frames(
+    dir: &str,
+    input: &[Vec<u8>],
+    asteroids: &[(i32, i32)],
+    counts: &[usize],
+    station: (i32, i32),
+    order: &[(i32, i32)],
+) -> io::Result<()> {
+    fs::create_dir_all(dir)?;
+    let mut vaporized = HashSet::new();
+    for (i, a) in order.iter().enumerate() {
+        let frame = render_svg(input, asteroids, counts, station, &vaporized, Some(*a));
+        fs::write(Path::new(dir).join(format!("frame{:04}.svg", i + 1)), frame)?;
+        vaporized.insert(*a);
+    }
+    let frame = render_svg(input, asteroids, counts, station, &vaporized, None);
+    fs::write(Path::new(dir).join(format!("frame{:04}.svg", order.len() + 1)), frame)
+}
+
+fn ordinal(n: usize) -> String {
+    let suffix = match (n % 10, n % 100) {
+        (_, 11..=13) => "th",
+        (1, _) => "st",
+        (2, _) => "nd",
+        (3, _) => "rd",
+        _ => "th",
+    };
+    format!("{}{}", n, suffix)
+}
+
+fn parse_map(input: &str) -> Vec<Vec<u8>> {
+    let input: Vec<&str> = input.split('\n').map(|l| l.trim_end_matches('\r')).filter(|l| !l.is_empty()).collect();
+    input.iter().map(|s| s.bytes().map(|b| if b == b'.' { 0 } else { 1 }).collect()).collect()
+}
+
+fn usage() -> ! {
+    eprintln!("Usage: day10 [--nth <N>]... [--order] [--svg <FILE>] [--frames <DIR>] < INPUT");
+    eprintln!("       day10 --bench <SIZE>");
+    std::process::exit(1);
 }
 
 fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().skip(1).collect();
+    if args.len() == 2 && args[0] == "--bench" {
+        benchmark(args[1].parse().unwrap_or_else(|_| usage()));
+        return Ok(());
+    }
+
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
 
-    let input: Vec<&str> = input.split('\n').filter(|l| *l != "").collect();
-    let input: Vec<Vec<u8>> = input.iter().map(|s| s.as_bytes().to_vec()).collect();
-    let input: Vec<Vec<u8>> = input.iter().map(|v| v.iter().map(|b| if *b == b'.' { 0 } else { 1 }).collect()).collect();
-
-    let num_x = input[0].len() as i32;
-    let num_y = input.len() as i32;
-    let max_dim = if num_x > num_y { num_x } else { num_y };
+    let input = parse_map(&input);
 
+    let asteroids = get_asteroids(&input);
     let mut max_count = 0;
     let mut location = (0, 0);
-    for q in 0..num_y {
-        for p in 0..num_x {
-            if input[q as usize][p as usize] == 1 {
-                let count = get_num_in_sight(&input, p, q);
-                if count > max_count {
-                    max_count = count;
-                    location = (p, q);
-                }
-            }
+    let counts = get_visibility_counts(&asteroids);
+    for (a, &count) in asteroids.iter().zip(counts.iter()) {
+        if count > max_count {
+            max_count = count;
+            location = *a;
         }
     }
     println!("Number of asteroids in sight from best position: {}", max_count);
 
-    let mut fractions: Vec<(i32, i32)> = get_unique_fractions(max_dim).iter().cloned().collect();
-    fractions.sort_by_key(|t| (vector_to_angle(*t) * 10000f64) as i64);
+    let order = get_vaporization_order(&input, location);
+    let print_nth = |n: usize| match order.get(n - 1) {
+        Some((xa, ya)) => println!("The position of the {} asteroid: {}", ordinal(n), xa * 100 + ya),
+        None => println!("There is no {} asteroid, only {} can be vaporized", ordinal(n), order.len()),
+    };
+    print_nth(200);
 
-    let mut field = input.clone();
-    let mut index = 0;
-    let mut asteroid_count = 0;
-    let mut xa = 0;
-    let mut ya = 0;
-    let (xc, yc) = location;
-
-    while asteroid_count < 200 {
-        let dir = fractions[index];
-
-        if let Some((p, q)) = asteroid_in_sight(&field, xc, yc, dir) {
-            xa = p;
-            ya = q;
-            field[q as usize][p as usize] = 0;
-            asteroid_count += 1;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match &arg[..] {
+            "--nth" => print_nth(args.next().and_then(|n| n.parse().ok()).filter(|n| *n > 0).unwrap_or_else(|| usage())),
+            "--order" => {
+                for (i, (xa, ya)) in order.iter().enumerate() {
+                    println!("{}: ({}, {})", i + 1, xa, ya);
+                }
+            }
+            "--svg" => {
+                let filename = args.next().unwrap_or_else(|| usage());
+                fs::write(filename, render_svg(&input, &asteroids, &counts, location, &HashSet::new(), None))?;
+            }
+            "--frames" => {
+                let dir = args.next().unwrap_or_else(|| usage());
+                write_frames(dir, &input, &asteroids, &counts, location, &order)?;
+            }
+            _ => usage(),
         }
-
-        index += 1;
-        index %= fractions.len();
     }
-    println!("The position of the 200th asteroid: {}", xa * 100 + ya);
+
     Ok(())
 }
@@ -1,13 +1,15 @@
 use std::io;
 use std::io::prelude::*;
+use std::thread;
+use std::time::{Duration, Instant};
 
 use nom::{
     bytes::complete::tag,
     character::complete::{anychar, char, digit1},
-    combinator::{map, map_res, opt, recognize},
+    combinator::{map, map_opt, map_res, opt, recognize},
     IResult,
     multi::separated_list1,
-    sequence::{delimited, pair, preceded, terminated, tuple}
+    sequence::{delimited, pair, preceded}
 };
 
 type Coordinate = i64;
@@ -24,182 +26,238 @@ fn lcm(a: u64, b: u64) -> u64 {
     (a * b) / gcd(a, b)
 }
 
-fn lcm3(a: u64, b: u64, c: u64) -> u64 {
-    lcm(lcm(a, b), c)
+fn lcm_all(values: &[u64]) -> u64 {
+    values.iter().fold(1, |acc, v| lcm(acc, *v))
 }
 
+/// A body moving along `N` independent axes.
 #[derive(Clone, Copy, Debug)]
-struct Moon {
-    x: Coordinate,
-    y: Coordinate,
-    z: Coordinate,
-    vx: Coordinate,
-    vy: Coordinate,
-    vz: Coordinate,
-}
-
-impl Moon {
-    fn new(x: Coordinate, y: Coordinate, z: Coordinate) -> Moon {
-        Moon {
-            x,
-            y,
-            z,
-            vx: 0,
-            vy: 0,
-            vz: 0,
+struct Body<const N: usize> {
+    position: [Coordinate; N],
+    velocity: [Coordinate; N],
+}
+
+impl<const N: usize> Body<N> {
+    fn new(position: [Coordinate; N]) -> Body<N> {
+        Body {
+            position,
+            velocity: [0; N],
         }
     }
 
     fn get_potential_energy(&self) -> Coordinate {
-        self.x.abs() + self.y.abs() + self.z.abs()
+        self.position.iter().map(|c| c.abs()).sum()
     }
 
     fn get_kinetic_energy(&self) -> Coordinate {
-        self.vx.abs() + self.vy.abs() + self.vz.abs()
+        self.velocity.iter().map(|v| v.abs()).sum()
     }
 
     fn get_energy(&self) -> Coordinate {
         self.get_potential_energy() * self.get_kinetic_energy()
     }
 
-    fn get_coordinates(&self) -> (Coordinate, Coordinate, Coordinate) {
-        (self.x, self.y, self.z)
-    }
-
     fn apply_velocity(&mut self) {
-        self.x += self.vx;
-        self.y += self.vy;
-        self.z += self.vz;
+        for (c, v) in self.position.iter_mut().zip(self.velocity.iter()) {
+            *c += v;
+        }
     }
 
-    fn apply_attraction_from(&mut self, x: Coordinate, y: Coordinate, z: Coordinate) {
-        if self.x < x {
-            self.vx += 1;
-        } else if self.x > x {
-            self.vx -= 1;
-        }
-        if self.y < y {
-            self.vy += 1;
-        } else if self.y > y {
-            self.vy -= 1;
-        }
-        if self.z < z {
-            self.vz += 1;
-        } else if self.z > z {
-            self.vz -= 1;
+    fn apply_attraction_from(&mut self, position: &[Coordinate; N]) {
+        for ((v, c), other) in self.velocity.iter_mut().zip(self.position.iter()).zip(position.iter()) {
+            *v += (other - c).signum();
         }
     }
 }
 
+/// Any number of bodies attracting each other along `N` axes.
 #[derive(Debug)]
-struct LunarSystem {
-    moons: Vec<Moon>,
-    x_cycled: Option<u64>,
-    y_cycled: Option<u64>,
-    z_cycled: Option<u64>,
-}
-
-impl LunarSystem {
-    fn new(moons: &Vec<Moon>) -> LunarSystem {
-        LunarSystem {
-            moons: moons.clone(),
-            x_cycled: None,
-            y_cycled: None,
-            z_cycled: None,
+struct System<const N: usize> {
+    bodies: Vec<Body<N>>,
+    cycled: [Option<u64>; N],
+}
+
+impl<const N: usize> System<N> {
+    fn new(bodies: &[Body<N>]) -> System<N> {
+        System {
+            bodies: bodies.to_vec(),
+            cycled: [None; N],
         }
     }
 
     fn get_energy(&self) -> Coordinate {
-        self.moons.iter().map(|m| m.get_energy()).sum()
+        self.bodies.iter().map(|b| b.get_energy()).sum()
     }
 
     fn step(&mut self) {
-        for i in 0..self.moons.len() {
-            for j in i+1..self.moons.len() {
-                let (x, y, z) = self.moons[j].get_coordinates();
-                self.moons[i].apply_attraction_from(x, y, z);
-                let (x, y, z) = self.moons[i].get_coordinates();
-                self.moons[j].apply_attraction_from(x, y, z);
+        for i in 0..self.bodies.len() {
+            for j in i+1..self.bodies.len() {
+                let position = self.bodies[j].position;
+                self.bodies[i].apply_attraction_from(&position);
+                let position = self.bodies[i].position;
+                self.bodies[j].apply_attraction_from(&position);
             }
         }
-        for moon in self.moons.iter_mut() {
-            moon.apply_velocity();
+        for body in self.bodies.iter_mut() {
+            body.apply_velocity();
         }
     }
 
-    fn get_cycles(&mut self) -> (u64, u64, u64) {
+    fn get_cycles(&mut self) -> [u64; N] {
         let mut i = 0;
-        loop {
-            let mut done = true; 
+        while self.cycled.iter().any(|c| c.is_none()) {
             self.step();
             i += 1;
-            if let None = self.x_cycled {
-                if self.zero_x_velocity() {
-                    self.x_cycled = Some(i);
-                } else {
-                    done = false;
-                }
-            }
-            if let None = self.y_cycled {
-                if self.zero_y_velocity() {
-                    self.y_cycled = Some(i);
-                } else {
-                    done = false;
+            for axis in 0..N {
+                if self.cycled[axis].is_none() && self.zero_velocity(axis) {
+                    self.cycled[axis] = Some(i);
                 }
             }
-            if let None = self.z_cycled {
-                if self.zero_z_velocity() {
-                    self.z_cycled = Some(i);
-                } else {
-                    done = false;
-                }
-            }
-            if done {
-                break;
-            }
         }
-        return (
-            self.x_cycled.unwrap(),
-            self.y_cycled.unwrap(),
-            self.z_cycled.unwrap()
-        )
+        self.cycled.map(|c| c.unwrap())
+    }
+
+    fn zero_velocity(&self, axis: usize) -> bool {
+        self.bodies.iter().all(|b| b.velocity[axis] == 0)
+    }
+
+    /// The exact number of steps after which every axis is back in its
+    /// starting state, comparing both positions and velocities, and how long
+    /// finding it took. Axes don't influence each other, so every axis is
+    /// searched on its own thread.
+    fn get_periods(&self) -> Result<[(u64, Duration); N], String> {
+        let axes = (0..N).map(|axis| AxisState::new(&self.bodies, axis)).collect::<Result<Vec<_>, _>>()?;
+        thread::scope(|scope| {
+            let handles: Vec<_> = axes
+                .into_iter()
+                .map(|axis| {
+                    scope.spawn(move || {
+                        let start = Instant::now();
+                        axis.get_period().map(|p| (p, start.elapsed()))
+                    })
+                })
+                .collect();
+            let periods = handles.into_iter().map(|h| h.join().unwrap()).collect::<Result<Vec<_>, _>>()?;
+            Ok(periods.try_into().unwrap())
+        })
+    }
+}
+
+/// The positions and velocities of all bodies along a single axis, kept
+/// small so that the state of even a large system stays in cache.
+#[derive(Clone, PartialEq)]
+struct AxisState {
+    axis: usize,
+    positions: Vec<i32>,
+    velocities: Vec<i32>,
+}
+
+impl AxisState {
+    fn new<const N: usize>(bodies: &[Body<N>], axis: usize) -> Result<AxisState, String> {
+        let narrow = |c: Coordinate| i32::try_from(c).map_err(|_| format!("Coordinate {} on axis {} is out of range", c, axis));
+        Ok(AxisState {
+            axis,
+            positions: bodies.iter().map(|b| narrow(b.position[axis])).collect::<Result<_, _>>()?,
+            velocities: bodies.iter().map(|b| narrow(b.velocity[axis])).collect::<Result<_, _>>()?,
+        })
     }
 
-    fn zero_x_velocity(&mut self) -> bool {
-        for m in self.moons.iter() {
-            if m.vx != 0 {
-                return false;
+    fn step(&mut self) -> Result<(), String> {
+        let overflow = || format!("Overflow while simulating axis {}", self.axis);
+        for i in 0..self.positions.len() {
+            for j in i+1..self.positions.len() {
+                let d = self.positions[j].checked_sub(self.positions[i]).ok_or_else(overflow)?.signum();
+                self.velocities[i] = self.velocities[i].checked_add(d).ok_or_else(overflow)?;
+                self.velocities[j] = self.velocities[j].checked_sub(d).ok_or_else(overflow)?;
             }
         }
-        true
+        for (p, v) in self.positions.iter_mut().zip(self.velocities.iter()) {
+            *p = p.checked_add(*v).ok_or_else(overflow)?;
+        }
+        Ok(())
     }
 
-    fn zero_y_velocity(&mut self) -> bool {
-        for m in self.moons.iter() {
-            if m.vy != 0 {
-                return false;
+    fn get_period(&self) -> Result<u64, String> {
+        let mut state = self.clone();
+        let mut i = 0;
+        loop {
+            state.step()?;
+            i += 1;
+            if state == *self {
+                return Ok(i);
             }
         }
-        true
     }
+}
+
+type Moon = Body<3>;
+type LunarSystem = System<3>;
+
+#[test]
+fn test_example_system() {
+    let moons = [
+        Moon::new([-1, 0, 2]),
+        Moon::new([2, -10, -7]),
+        Moon::new([4, -8, 8]),
+        Moon::new([3, 5, -1]),
+    ];
+    let mut system = LunarSystem::new(&moons);
+    for _i in 0..10 {
+        system.step();
+    }
+    assert_eq!(system.get_energy(), 179);
+    assert_eq!(lcm_all(&LunarSystem::new(&moons).get_cycles()) * 2, 2772);
+    let periods = LunarSystem::new(&moons).get_periods().unwrap().map(|(p, _)| p);
+    assert_eq!(lcm_all(&periods), 2772);
 
-    fn zero_z_velocity(&mut self) -> bool {
-        for m in self.moons.iter() {
-            if m.vz != 0 {
-                return false;
+    let cycles = LunarSystem::new(&moons).get_cycles();
+    for (axis, cycle) in cycles.iter().enumerate() {
+        let bodies: Vec<Body<1>> = moons.iter().map(|m| Body::new([m.position[axis]])).collect();
+        assert_eq!(System::new(&bodies).get_cycles(), [*cycle]);
+    }
+}
+
+#[test]
+fn test_axis_periods() {
+    // Steps the full system, checking each axis against its starting state.
+    let full_state_periods = |moons: &[Moon]| {
+        let mut system = LunarSystem::new(moons);
+        let mut periods = [None; 3];
+        let mut i = 0;
+        while periods.iter().any(|p: &Option<u64>| p.is_none()) {
+            system.step();
+            i += 1;
+            for (axis, period) in periods.iter_mut().enumerate() {
+                let back = system.bodies.iter().zip(moons.iter()).all(|(a, b)| {
+                    a.position[axis] == b.position[axis] && a.velocity[axis] == b.velocity[axis]
+                });
+                if period.is_none() && back {
+                    *period = Some(i);
+                }
             }
         }
-        true
-    }
+        periods.map(|p| p.unwrap())
+    };
+
+    let moons = [
+        Moon::new([-8, -10, 0]),
+        Moon::new([5, 5, 10]),
+        Moon::new([2, -7, 3]),
+        Moon::new([9, -8, -3]),
+    ];
+    let periods = LunarSystem::new(&moons).get_periods().unwrap().map(|(p, _)| p);
+    assert_eq!(periods, full_state_periods(&moons));
+    assert_eq!(lcm_all(&periods), 4686774924);
+
+    let moons = [Moon::new([1 << 40, 0, 0]), Moon::new([0, 0, 0])];
+    assert!(LunarSystem::new(&moons).get_periods().is_err());
+    let moons = [Moon::new([i32::MAX as Coordinate, 0, 0]), Moon::new([i32::MIN as Coordinate, 0, 0])];
+    assert!(LunarSystem::new(&moons).get_periods().is_err());
 }
 
-fn parse_coordinate_triple(input: &str) -> IResult <&str, (Coordinate, Coordinate, Coordinate)> {
+fn parse_coordinates<const N: usize>(input: &str) -> IResult <&str, [Coordinate; N]> {
     let prefixed_coordinate = preceded(pair(anychar, char('=')), parse_coordinate);
-    tuple((
-        terminated(preceded(pair(anychar, char('=')), parse_coordinate), tag(", ")),
-        terminated(preceded(pair(anychar, char('=')), parse_coordinate), tag(", ")),
-        prefixed_coordinate,
-    ))(input)
+    map_opt(separated_list1(tag(", "), prefixed_coordinate), |v| v.try_into().ok())(input)
 }
 
 fn parse_coordinate(input: &str) -> IResult <&str, Coordinate> {
@@ -207,13 +265,13 @@ fn parse_coordinate(input: &str) -> IResult <&str, Coordinate> {
     map_res(recognize(parser), |s: &str| s.parse::<Coordinate>())(input)
 }
 
-fn parse_moon(input: &str) -> IResult <&str, Moon> {
-    let parser = delimited(char('<'), parse_coordinate_triple, char('>'));
-    map(parser, |t: (Coordinate, Coordinate, Coordinate)| Moon::new(t.0, t.1, t.2))(input)
+fn parse_body<const N: usize>(input: &str) -> IResult <&str, Body<N>> {
+    let parser = delimited(char('<'), parse_coordinates, char('>'));
+    map(parser, Body::new)(input)
 }
 
 fn parse_moons(input: &str) -> IResult <&str, Vec<Moon>> {
-    separated_list1(char('\n'), parse_moon)(input)
+    separated_list1(char('\n'), parse_body)(input)
 }
 
 fn main() -> io::Result<()> {
@@ -228,9 +286,22 @@ fn main() -> io::Result<()> {
     }
     println!("Total energy in the system after 100 steps: {}", system.get_energy());
 
-    let mut system = LunarSystem::new(&input);
-    let (cx, cy, cz) = system.get_cycles();
-    println!("The number of steps before the first cycle: {}", lcm3(cx, cy, cz) * 2);
+    let timed = match LunarSystem::new(&input).get_periods() {
+        Ok(timed) => timed,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+    };
+    let periods = timed.map(|(p, _)| p);
+    let periods_text: Vec<String> = timed.iter().map(|(p, t)| format!("{} ({:?})", p, t)).collect();
+    println!("The period of each axis: {}", periods_text.join(", "));
+    println!("The number of steps before the first cycle: {}", lcm_all(&periods));
+
+    let halves = LunarSystem::new(&input).get_cycles();
+    if halves.iter().zip(periods.iter()).any(|(h, p)| h * 2 != *p) {
+        println!("The halving shortcut disagrees: {}", lcm_all(&halves) * 2);
+    }
 
     Ok(())
 }
@@ -1,23 +1,35 @@
 use std::io;
 use std::io::prelude::*;
+use std::time::Duration;
 
-use intcode::Program;
 use intcode::Number;
 
+use adventofcode_rust_2019::bounded::{BoundedProgram, RunStatus};
+
+const MAX_STEPS: u64 = 10_000_000;
+const TIME_LIMIT: Duration = Duration::from_secs(10);
+
+fn check_status(status: RunStatus) -> RunStatus {
+    match status {
+        RunStatus::Exhausted => panic!("Program did not stop within {} steps or {:?}", MAX_STEPS, TIME_LIMIT),
+        RunStatus::Fault { address, reason } => panic!("Program faulted at {}: {}", address, reason),
+        status => status,
+    }
+}
+
 fn main() -> io::Result<()> {
     let mut input = String::new();
     io::stdin().lock().read_to_string(&mut input).unwrap();
     let input = input.split('\n').next().unwrap();
     let mut input: Vec<Number> = input.split(',').map(|s| s.parse::<Number>().unwrap()).collect();
 
-    let mut program = Program::new(input.clone());
-    program.run_till_halted_or_blocked();
+    let mut program = BoundedProgram::new(input.clone(), MAX_STEPS, TIME_LIMIT);
+    check_status(program.run());
 
     let mut num_blocks = 0;
-    while program.has_output() {
-        program.get_output().unwrap();
+    while program.get_output().unwrap().is_some() {
         program.get_output().unwrap();
-        if program.get_output().unwrap() == 2 {
+        if program.get_output().unwrap() == Some(2) {
             num_blocks += 1;
         }
     }
@@ -25,18 +37,17 @@ fn main() -> io::Result<()> {
     println!("Number of blocks in the game: {}", num_blocks);
 
     input[0] = 2;
-    let mut program = Program::new(input);
+    let mut program = BoundedProgram::new(input, MAX_STEPS, TIME_LIMIT);
     let mut paddle_x = 0;
     let mut ball_x = 0;
     let mut score = 0;
 
     loop {
-        program.run_till_halted_or_blocked();
+        let status = check_status(program.run());
 
-        while program.has_output() {
-            let x = program.get_output().unwrap();
-            let y = program.get_output().unwrap();
-            let tile = program.get_output().unwrap();
+        while let Some(x) = program.get_output().unwrap() {
+            let y = program.get_output().unwrap().unwrap();
+            let tile = program.get_output().unwrap().unwrap();
 
             if x == -1 && y == 0 {
                 num_blocks -= 1;
@@ -50,16 +61,16 @@ fn main() -> io::Result<()> {
             }
         }
 
-        if num_blocks == 0 {
+        if num_blocks == 0 || status == RunStatus::Halted {
             break;
         }
 
         if paddle_x < ball_x {
-            program.push_input(1);
+            program.push_input(1).unwrap();
         } else if paddle_x > ball_x {
-            program.push_input(-1);
+            program.push_input(-1).unwrap();
         } else {
-            program.push_input(0);
+            program.push_input(0).unwrap();
         }
     }
 
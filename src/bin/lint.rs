@@ -0,0 +1,65 @@
+use std::env;
+use std::fs;
+use std::io;
+
+use intcode::Number;
+
+use adventofcode_rust_2019::analysis::analyze;
+
+fn parse_program(input: &str) -> Option<Vec<Number>> {
+    let input = input.split('\n').next().unwrap();
+    if !input.contains(',') {
+        return None;
+    }
+    input.split(',').map(|s| s.trim().parse::<Number>().ok()).collect()
+}
+
+fn lint(filename: &str) -> io::Result<()> {
+    let input = fs::read_to_string(filename)?;
+    let program = match parse_program(&input) {
+        Some(p) => p,
+        None => {
+            println!("{}: not an Intcode program, skipped", filename);
+            return Ok(());
+        }
+    };
+
+    let analysis = analyze(&program);
+    let unreachable: usize = analysis.unreachable.iter().map(|r| r.len()).sum();
+    let unreachable = if analysis.unreachable_is_exact() {
+        format!("{} of {} cells unreachable", unreachable, program.len())
+    } else {
+        format!("{} of {} cells not reached, but may be through dynamic jumps", unreachable, program.len())
+    };
+
+    println!(
+        "{}: {} reachable instructions in {} blocks, {}, {} dynamic jumps",
+        filename,
+        analysis.instructions.len(),
+        analysis.blocks.len(),
+        unreachable,
+        analysis.dynamic_jumps().len(),
+    );
+    for issue in analysis.issues.iter() {
+        println!("    {}", issue);
+    }
+
+    Ok(())
+}
+
+fn main() -> io::Result<()> {
+    let mut filenames: Vec<String> = env::args().skip(1).collect();
+
+    if filenames.is_empty() {
+        for entry in fs::read_dir("data")? {
+            filenames.push(entry?.path().to_string_lossy().to_string());
+        }
+        filenames.sort();
+    }
+
+    for filename in filenames.iter() {
+        lint(filename)?;
+    }
+
+    Ok(())
+}
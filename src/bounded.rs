@@ -0,0 +1,232 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use intcode::Number;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum RunStatus {
+    NotStarted,
+    Halted,
+    BlockedOnInput,
+    Exhausted,
+    Fault { address: usize, reason: String },
+}
+
+impl RunStatus {
+    /// Whether the program can't run any further.
+    pub fn is_dead(&self) -> bool {
+        matches!(self, RunStatus::Halted | RunStatus::Exhausted | RunStatus::Fault { .. })
+    }
+}
+
+/// The wall clock is only checked once every this many instructions.
+const CLOCK_INTERVAL: u64 = 4096;
+
+/// The number of memory cells a program may use. Writes beyond this fault
+/// instead of growing the memory.
+const MAX_MEMORY: usize = 1 << 24;
+
+/// An Intcode machine that stops every call to `run` after a number of
+/// instructions or an amount of wall-clock time, whichever comes first. It has
+/// its own stepper, since the VM in the intcode crate can only be run until it
+/// halts or blocks. Once a program is exhausted or faults it is dead: every
+/// later call to `run` reports the same status, and input and output fail.
+pub struct BoundedProgram {
+    memory: Vec<Number>,
+    ip: usize,
+    relative_base: Number,
+    input: VecDeque<Number>,
+    output: VecDeque<Number>,
+    max_steps: u64,
+    time_limit: Duration,
+    status: RunStatus,
+}
+
+impl BoundedProgram {
+    pub fn new(memory: Vec<Number>, max_steps: u64, time_limit: Duration) -> Self {
+        BoundedProgram {
+            memory,
+            ip: 0,
+            relative_base: 0,
+            input: VecDeque::new(),
+            output: VecDeque::new(),
+            max_steps,
+            time_limit,
+            status: RunStatus::NotStarted,
+        }
+    }
+
+    pub fn status(&self) -> &RunStatus {
+        &self.status
+    }
+
+    pub fn push_input(&mut self, input: Number) -> Result<(), RunStatus> {
+        if self.status.is_dead() {
+            return Err(self.status.clone());
+        }
+        self.input.push_back(input);
+        Ok(())
+    }
+
+    /// Output is still available after the program halts, but not once it
+    /// has been exhausted or has faulted.
+    pub fn get_output(&mut self) -> Result<Option<Number>, RunStatus> {
+        match self.status {
+            RunStatus::Exhausted | RunStatus::Fault { .. } => Err(self.status.clone()),
+            _ => Ok(self.output.pop_front()),
+        }
+    }
+
+    pub fn run(&mut self) -> RunStatus {
+        if self.status.is_dead() {
+            return self.status.clone();
+        }
+
+        let start = Instant::now();
+        let mut steps = 0;
+        self.status = loop {
+            if steps == self.max_steps {
+                break RunStatus::Exhausted;
+            }
+            if steps % CLOCK_INTERVAL == CLOCK_INTERVAL - 1 && start.elapsed() > self.time_limit {
+                break RunStatus::Exhausted;
+            }
+            let address = self.ip;
+            match self.step() {
+                Ok(None) => steps += 1,
+                Ok(Some(status)) => break status,
+                Err(reason) => break RunStatus::Fault { address, reason },
+            }
+        };
+        self.status.clone()
+    }
+
+    fn read(&mut self, address: usize) -> Number {
+        self.memory.get(address).copied().unwrap_or(0)
+    }
+
+    fn parameter_address(&mut self, n: usize) -> Result<usize, String> {
+        let mode = self.read(self.ip) / [100, 1000, 10000][n] % 10;
+        let parameter = self.read(self.ip + n + 1);
+        let address = match mode {
+            0 => parameter,
+            1 => return Ok(self.ip + n + 1),
+            2 => self
+                .relative_base
+                .checked_add(parameter)
+                .ok_or_else(|| format!("overflow adding {} to relative base {}", parameter, self.relative_base))?,
+            _ => return Err(format!("invalid mode {} for parameter {}", mode, n)),
+        };
+        usize::try_from(address).map_err(|_| format!("negative address {}", address))
+    }
+
+    fn value(&mut self, n: usize) -> Result<Number, String> {
+        let address = self.parameter_address(n)?;
+        Ok(self.read(address))
+    }
+
+    fn write(&mut self, n: usize, value: Number) -> Result<(), String> {
+        if self.read(self.ip) / [100, 1000, 10000][n] % 10 == 1 {
+            return Err(format!("write through immediate parameter {}", n));
+        }
+        let address = self.parameter_address(n)?;
+        if address >= MAX_MEMORY {
+            return Err(format!("write to address {} out of range", address));
+        }
+        if address >= self.memory.len() {
+            self.memory.resize(address + 1, 0);
+        }
+        self.memory[address] = value;
+        Ok(())
+    }
+
+    fn jump(&mut self, target: Number) -> Result<(), String> {
+        self.ip = usize::try_from(target).map_err(|_| format!("jump to negative address {}", target))?;
+        Ok(())
+    }
+
+    /// Executes a single instruction, returning the status if it stops the
+    /// run.
+    fn step(&mut self) -> Result<Option<RunStatus>, String> {
+        let opcode = self.read(self.ip) % 100;
+        match opcode {
+            1 | 2 => {
+                let (a, b) = (self.value(0)?, self.value(1)?);
+                let result = if opcode == 1 { a.checked_add(b) } else { a.checked_mul(b) };
+                self.write(2, result.ok_or_else(|| format!("overflow computing {} and {}", a, b))?)?;
+                self.ip += 4;
+            }
+            3 => match self.input.pop_front() {
+                Some(i) => {
+                    self.write(0, i)?;
+                    self.ip += 2;
+                }
+                None => return Ok(Some(RunStatus::BlockedOnInput)),
+            },
+            4 => {
+                let value = self.value(0)?;
+                self.output.push_back(value);
+                self.ip += 2;
+            }
+            5 | 6 => {
+                if (self.value(0)? != 0) == (opcode == 5) {
+                    let target = self.value(1)?;
+                    self.jump(target)?;
+                } else {
+                    self.ip += 3;
+                }
+            }
+            7 | 8 => {
+                let (a, b) = (self.value(0)?, self.value(1)?);
+                self.write(2, if opcode == 7 { (a < b) as Number } else { (a == b) as Number })?;
+                self.ip += 4;
+            }
+            9 => {
+                let offset = self.value(0)?;
+                self.relative_base = self
+                    .relative_base
+                    .checked_add(offset)
+                    .ok_or_else(|| format!("overflow adding {} to relative base {}", offset, self.relative_base))?;
+                self.ip += 2;
+            }
+            99 => return Ok(Some(RunStatus::Halted)),
+            _ => return Err(format!("invalid opcode {}", self.read(self.ip))),
+        }
+        Ok(None)
+    }
+}
+
+#[test]
+fn test_run_status() {
+    let mut p = BoundedProgram::new(vec![3, 9, 4, 9, 99, 0, 0, 0, 0, 0], 1000, Duration::from_secs(5));
+    assert_eq!(p.status(), &RunStatus::NotStarted);
+    assert_eq!(p.run(), RunStatus::BlockedOnInput);
+    p.push_input(7).unwrap();
+    assert_eq!(p.run(), RunStatus::Halted);
+    assert_eq!(p.get_output(), Ok(Some(7)));
+    assert_eq!(p.push_input(8), Err(RunStatus::Halted));
+
+    let mut p = BoundedProgram::new(vec![1105, 1, 0], 1000, Duration::from_secs(5));
+    assert_eq!(p.run(), RunStatus::Exhausted);
+    assert_eq!(p.run(), RunStatus::Exhausted);
+    assert_eq!(p.get_output(), Err(RunStatus::Exhausted));
+
+    let mut p = BoundedProgram::new(vec![1105, 1, 0], u64::MAX, Duration::from_millis(20));
+    assert_eq!(p.run(), RunStatus::Exhausted);
+
+    let mut p = BoundedProgram::new(vec![104, 1, 1101, 1, 1, 5, 42], 1000, Duration::from_secs(5));
+    let fault = RunStatus::Fault { address: 6, reason: String::from("invalid opcode 42") };
+    assert_eq!(p.run(), fault);
+    assert_eq!(p.push_input(1), Err(fault));
+
+    let mut p = BoundedProgram::new(vec![1101, 1, 1, 1 << 45, 99], 1000, Duration::from_secs(5));
+    let reason = format!("write to address {} out of range", 1u64 << 45);
+    assert_eq!(p.run(), RunStatus::Fault { address: 0, reason });
+
+    let mut p = BoundedProgram::new(vec![109, Number::MAX, 109, 1, 99], 1000, Duration::from_secs(5));
+    let reason = format!("overflow adding 1 to relative base {}", Number::MAX);
+    assert_eq!(p.run(), RunStatus::Fault { address: 2, reason });
+
+    let mut p = BoundedProgram::new(vec![109, Number::MAX, 204, 1, 99], 1000, Duration::from_secs(5));
+    assert!(matches!(p.run(), RunStatus::Fault { address: 2, .. }));
+}
@@ -0,0 +1,217 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll, Wake, Waker};
+
+use intcode::Number;
+use intcode::Program;
+
+struct Channel {
+    queue: VecDeque<Number>,
+    waker: Option<Waker>,
+    senders: usize,
+}
+
+impl Channel {
+    fn wake(&mut self) {
+        if let Some(waker) = self.waker.take() {
+            waker.wake();
+        }
+    }
+}
+
+pub struct Sender {
+    channel: Rc<RefCell<Channel>>,
+}
+
+pub struct Receiver {
+    channel: Rc<RefCell<Channel>>,
+}
+
+pub fn channel() -> (Sender, Receiver) {
+    let channel = Rc::new(RefCell::new(Channel {
+        queue: VecDeque::new(),
+        waker: None,
+        senders: 1,
+    }));
+    (Sender { channel: channel.clone() }, Receiver { channel })
+}
+
+impl Sender {
+    pub fn send(&self, value: Number) {
+        let mut channel = self.channel.borrow_mut();
+        channel.queue.push_back(value);
+        channel.wake();
+    }
+}
+
+impl Clone for Sender {
+    fn clone(&self) -> Self {
+        self.channel.borrow_mut().senders += 1;
+        Sender { channel: self.channel.clone() }
+    }
+}
+
+impl Drop for Sender {
+    fn drop(&mut self) {
+        let mut channel = self.channel.borrow_mut();
+        channel.senders -= 1;
+        channel.wake();
+    }
+}
+
+impl Receiver {
+    pub fn try_recv(&mut self) -> Option<Number> {
+        self.channel.borrow_mut().queue.pop_front()
+    }
+
+    /// Resolves to the next value, or to `None` once the channel is empty
+    /// and every sender has been dropped.
+    pub fn recv(&mut self) -> Recv<'_> {
+        Recv { receiver: self }
+    }
+}
+
+pub struct Recv<'a> {
+    receiver: &'a mut Receiver,
+}
+
+impl Future for Recv<'_> {
+    type Output = Option<Number>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let mut channel = self.receiver.channel.borrow_mut();
+        if let Some(value) = channel.queue.pop_front() {
+            Poll::Ready(Some(value))
+        } else if channel.senders == 0 {
+            Poll::Ready(None)
+        } else {
+            channel.waker = Some(cx.waker().clone());
+            Poll::Pending
+        }
+    }
+}
+
+/// Runs a program, feeding it from `input` and sending everything it outputs
+/// to `output`. Yields whenever the program blocks on an empty input, and
+/// finishes when it halts or when its input is closed.
+pub async fn run_machine(mut program: Program, mut input: Receiver, output: Sender) -> Program {
+    loop {
+        program.run_till_halted_or_blocked();
+        while let Some(value) = program.get_output() {
+            output.send(value);
+        }
+        if program.halted() {
+            return program;
+        }
+        match input.recv().await {
+            Some(value) => program.push_input(value),
+            None => return program,
+        }
+    }
+}
+
+struct TaskWaker {
+    id: usize,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Wake for TaskWaker {
+    fn wake(self: Arc<Self>) {
+        self.ready.lock().unwrap().push_back(self.id);
+    }
+}
+
+/// A single-threaded executor that only polls tasks that have been woken.
+#[derive(Default)]
+pub struct Executor {
+    tasks: Vec<Option<Pin<Box<dyn Future<Output = ()>>>>>,
+    ready: Arc<Mutex<VecDeque<usize>>>,
+}
+
+impl Executor {
+    pub fn new() -> Self {
+        Executor::default()
+    }
+
+    pub fn spawn<F: Future<Output = ()> + 'static>(&mut self, future: F) {
+        self.ready.lock().unwrap().push_back(self.tasks.len());
+        self.tasks.push(Some(Box::pin(future)));
+    }
+
+    /// Runs until no task can make progress, and returns the number of tasks
+    /// that are left waiting for input that will never come.
+    pub fn run(&mut self) -> usize {
+        loop {
+            let id = match self.ready.lock().unwrap().pop_front() {
+                Some(id) => id,
+                None => break,
+            };
+            let task = match self.tasks[id].as_mut() {
+                Some(task) => task,
+                None => continue,
+            };
+
+            let waker = Waker::from(Arc::new(TaskWaker {
+                id,
+                ready: self.ready.clone(),
+            }));
+            if task.as_mut().poll(&mut Context::from_waker(&waker)).is_ready() {
+                self.tasks[id] = None;
+            }
+        }
+        self.tasks.iter().filter(|t| t.is_some()).count()
+    }
+}
+
+#[test]
+fn test_feedback_loop() {
+    let program = vec![3,26,1001,26,-4,26,3,27,1002,27,2,27,1,27,26,27,4,27,1001,28,-1,28,1005,28,6,99,0,0,5];
+    let settings = [9, 8, 7, 6, 5];
+
+    let (mut senders, receivers): (Vec<Sender>, Vec<Receiver>) = settings.iter().map(|_| channel()).unzip();
+    for (sender, setting) in senders.iter().zip(settings.iter()) {
+        sender.send(*setting);
+    }
+    senders[0].send(0);
+    senders.rotate_left(1);
+
+    let mut executor = Executor::new();
+    let result = Rc::new(RefCell::new(None));
+    for (i, (receiver, sender)) in receivers.into_iter().zip(senders).enumerate() {
+        let machine = run_machine(Program::new(program.clone()), receiver, sender);
+        let result = result.clone();
+        executor.spawn(async move {
+            let program = machine.await;
+            if i == settings.len() - 1 {
+                *result.borrow_mut() = program.last_output();
+            }
+        });
+    }
+
+    assert_eq!(executor.run(), 0);
+    assert_eq!(*result.borrow(), Some(139629729));
+}
+
+#[test]
+fn test_many_machines() {
+    let increment = vec![3, 9, 1001, 9, 1, 9, 4, 9, 99, 0];
+
+    let mut executor = Executor::new();
+    let (first_sender, mut receiver) = channel();
+    for _ in 0..500 {
+        let (sender, next_receiver) = channel();
+        let machine = run_machine(Program::new(increment.clone()), receiver, sender);
+        executor.spawn(async move {
+            machine.await;
+        });
+        receiver = next_receiver;
+    }
+
+    first_sender.send(0);
+    assert_eq!(executor.run(), 0);
+    assert_eq!(receiver.try_recv(), Some(500));
+}
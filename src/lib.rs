@@ -0,0 +1,6 @@
+pub mod analysis;
+pub mod ascii;
+pub mod bounded;
+#[cfg(feature = "async")]
+pub mod driver;
+pub mod rng;
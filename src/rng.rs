@@ -0,0 +1,24 @@
+/// Small xorshift generator for generated test and benchmark inputs, so that
+/// every run can be reproduced from its seed alone.
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Rng(seed.wrapping_mul(0x9e3779b97f4a7c15) | 1)
+    }
+
+    pub fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        min + (self.next() % (max - min + 1) as u64) as i64
+    }
+}
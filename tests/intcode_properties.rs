@@ -0,0 +1,235 @@
+use std::collections::VecDeque;
+
+use intcode::Number;
+use intcode::Program;
+
+use adventofcode_rust_2019::rng::Rng;
+
+const NUM_CASES: u64 = 500;
+
+struct Case {
+    program: Vec<Number>,
+    inputs: Vec<Number>,
+}
+
+/// Generates a program that is guaranteed to halt: jumps only go forward, and
+/// writes only go to a data area after the final `99`, so the code can never
+/// be modified. Multiplications always take one small immediate operand to
+/// keep values far away from overflowing. The relative base is only adjusted
+/// by small immediate amounts, and the data area is padded on both sides by
+/// the furthest it can drift, so relative mode stays inside the data area.
+fn generate(rng: &mut Rng) -> Case {
+    let num_instructions = 1 + rng.below(30);
+    let num_data = 1 + rng.below(8);
+
+    let opcodes: Vec<Number> = (0..num_instructions)
+        .map(|_| [1, 2, 3, 4, 5, 6, 7, 8, 9][rng.below(9)])
+        .collect();
+    let max_drift = 3 * opcodes.iter().filter(|o| **o == 9).count() as Number;
+    let data_len = num_data + 2 * max_drift as usize;
+    let mut addresses = Vec::with_capacity(num_instructions + 1);
+    let mut address = 0;
+    for opcode in opcodes.iter() {
+        addresses.push(address);
+        address += match opcode {
+            1 | 2 | 7 | 8 => 4,
+            5 | 6 => 3,
+            _ => 2,
+        };
+    }
+    addresses.push(address);
+    let data_start = (address + 1) as Number;
+
+    let mut program = Vec::new();
+    let mut inputs = Vec::new();
+    for (index, &opcode) in opcodes.iter().enumerate() {
+        let mut modes = Vec::new();
+        let mut parameters = Vec::new();
+
+        let data_parameter = |rng: &mut Rng, mode: Number| match mode {
+            0 => data_start + rng.below(data_len) as Number,
+            _ => data_start + max_drift + rng.below(num_data) as Number,
+        };
+        let read = |rng: &mut Rng, modes: &mut Vec<Number>, parameters: &mut Vec<Number>| {
+            let mode = rng.range(0, 2);
+            modes.push(mode);
+            parameters.push(if mode == 1 { rng.range(-50, 50) } else { data_parameter(rng, mode) });
+        };
+        let write = |rng: &mut Rng, modes: &mut Vec<Number>, parameters: &mut Vec<Number>| {
+            let mode = if rng.below(2) == 0 { 0 } else { 2 };
+            modes.push(mode);
+            parameters.push(data_parameter(rng, mode));
+        };
+
+        match opcode {
+            2 => {
+                modes.push(1);
+                parameters.push(rng.range(-3, 3));
+                read(rng, &mut modes, &mut parameters);
+                write(rng, &mut modes, &mut parameters);
+            }
+            1 | 7 | 8 => {
+                read(rng, &mut modes, &mut parameters);
+                read(rng, &mut modes, &mut parameters);
+                write(rng, &mut modes, &mut parameters);
+            }
+            3 => {
+                write(rng, &mut modes, &mut parameters);
+                inputs.push(rng.range(-100, 100));
+            }
+            4 => read(rng, &mut modes, &mut parameters),
+            9 => {
+                modes.push(1);
+                parameters.push(rng.range(-3, 3));
+            }
+            _ => {
+                read(rng, &mut modes, &mut parameters);
+                modes.push(1);
+                parameters.push(addresses[index + 1 + rng.below(num_instructions - index)] as Number);
+            }
+        }
+
+        let mode_digits = modes.iter().rev().fold(0, |acc, m| acc * 10 + m);
+        program.push(mode_digits * 100 + opcode);
+        program.extend(parameters);
+    }
+    program.push(99);
+    for _ in 0..data_len {
+        program.push(rng.range(-20, 20));
+    }
+
+    Case { program, inputs }
+}
+
+/// A deliberately naive interpreter to compare the VM against. Returns the
+/// outputs and whether the program halted.
+fn reference_run(mut memory: Vec<Number>, inputs: &[Number]) -> (Vec<Number>, bool) {
+    let mut inputs: VecDeque<Number> = inputs.iter().cloned().collect();
+    let mut outputs = Vec::new();
+    let mut ip = 0;
+    let mut relative_base = 0;
+
+    loop {
+        let instruction = memory[ip];
+        let opcode = instruction % 100;
+        let address = |memory: &Vec<Number>, n: usize| -> usize {
+            let mode = instruction / [100, 1000, 10000][n] % 10;
+            let parameter = memory[ip + n + 1];
+            match mode {
+                0 => parameter as usize,
+                1 => ip + n + 1,
+                2 => (relative_base + parameter) as usize,
+                _ => panic!("invalid mode {}", mode),
+            }
+        };
+        let value = |memory: &Vec<Number>, n: usize| memory[address(memory, n)];
+
+        match opcode {
+            1 | 2 | 7 | 8 => {
+                let (a, b) = (value(&memory, 0), value(&memory, 1));
+                let target = address(&memory, 2);
+                memory[target] = match opcode {
+                    1 => a + b,
+                    2 => a * b,
+                    7 => (a < b) as Number,
+                    _ => (a == b) as Number,
+                };
+                ip += 4;
+            }
+            3 => match inputs.pop_front() {
+                Some(i) => {
+                    let target = address(&memory, 0);
+                    memory[target] = i;
+                    ip += 2;
+                }
+                None => return (outputs, false),
+            },
+            4 => {
+                outputs.push(value(&memory, 0));
+                ip += 2;
+            }
+            5 | 6 => {
+                if (value(&memory, 0) != 0) == (opcode == 5) {
+                    ip = value(&memory, 1) as usize;
+                } else {
+                    ip += 3;
+                }
+            }
+            9 => {
+                relative_base += value(&memory, 0);
+                ip += 2;
+            }
+            99 => return (outputs, true),
+            _ => panic!("invalid opcode {}", opcode),
+        }
+    }
+}
+
+fn run(program: &[Number], inputs: &[Number]) -> (Vec<Number>, bool) {
+    let mut p = Program::new(program.to_vec());
+    for i in inputs.iter() {
+        p.push_input(*i);
+    }
+    p.run_till_halted_or_blocked();
+
+    let mut outputs = Vec::new();
+    while let Some(o) = p.get_output() {
+        outputs.push(o);
+    }
+    (outputs, p.halted())
+}
+
+#[test]
+fn test_generated_programs_halt() {
+    for seed in 0..NUM_CASES {
+        let case = generate(&mut Rng::new(seed));
+        let (_outputs, halted) = run(&case.program, &case.inputs);
+        assert!(halted, "seed {}: {:?}", seed, case.program);
+    }
+}
+
+#[test]
+fn test_outputs_match_reference() {
+    for seed in 0..NUM_CASES {
+        let case = generate(&mut Rng::new(seed));
+        let expected = reference_run(case.program.clone(), &case.inputs);
+        assert_eq!(run(&case.program, &case.inputs), expected, "seed {}: {:?}", seed, case.program);
+    }
+}
+
+#[test]
+fn test_reference_blocks_like_vm() {
+    for seed in 0..NUM_CASES {
+        let case = generate(&mut Rng::new(seed));
+        let inputs = &case.inputs[..case.inputs.len() / 2];
+        let expected = reference_run(case.program.clone(), inputs);
+        assert_eq!(run(&case.program, inputs), expected, "seed {}: {:?}", seed, case.program);
+    }
+}
+
+/// The VM has no serialisable snapshot, so the round trip checked here is the
+/// one the days rely on: suspending on input and resuming later must give the
+/// same result as running with all input available up front.
+#[test]
+fn test_resume_matches_uninterrupted_run() {
+    for seed in 0..NUM_CASES {
+        let case = generate(&mut Rng::new(seed));
+        let expected = run(&case.program, &case.inputs);
+
+        let mut p = Program::new(case.program.clone());
+        let mut outputs = Vec::new();
+        p.run_till_halted_or_blocked();
+        for i in case.inputs.iter() {
+            while let Some(o) = p.get_output() {
+                outputs.push(o);
+            }
+            p.push_input(*i);
+            p.run_till_halted_or_blocked();
+        }
+        while let Some(o) = p.get_output() {
+            outputs.push(o);
+        }
+
+        assert_eq!((outputs, p.halted()), expected, "seed {}: {:?}", seed, case.program);
+    }
+}